@@ -14,6 +14,23 @@ interface IOrderBookTaskManager {
 
     event TaskCompleted(uint32 indexed taskIndex);
 
+    event OrderExpired(uint256 indexed orderIndex);
+
+    event OrderCancelled(uint256 indexed orderIndex, address indexed user);
+
+    // nextTaskIndex is the first task whose orderbook snapshot includes the amendment
+    event OrderAmended(
+        uint256 indexed orderIndex,
+        address indexed user,
+        uint256 amount_owned,
+        uint256 amount_not_owned,
+        uint256 timestamp,
+        uint32 nextTaskIndex
+    );
+
+    // taskIndex is the task of the live copy that replaces the dormant order
+    event OrderTriggered(uint256 indexed orderIndex, uint32 indexed taskIndex);
+
     event TaskChallengedSuccessfully(
         uint32 indexed taskIndex,
         address indexed challenger
@@ -32,10 +49,30 @@ interface IOrderBookTaskManager {
         address token_not_owned;
         address token_owned;
         uint256 slippage;
+        // 0 = limit, 1 = market, 2 = immediate-or-cancel, 3 = fill-or-kill, 4 = post-only
+        uint8 orderType;
         uint256 timestamp;
+        // Time after which the order can no longer be matched, 0 for good-til-cancelled
+        uint256 expiry;
+        // 0 = none, 1 = stop, 2 = take-profit. An order with a trigger stays dormant in the book
+        // until the last traded price of its market crosses the trigger price
+        uint8 triggerType;
+        // Trigger price in token1 per token0 base unit
+        uint256 triggerPriceNumerator;
+        uint256 triggerPriceDenominator;
+        // Size of each slice of amount_owned shown in the book, 0 to show the whole order.
+        // The rest of an iceberg order is held in reserve and shown one slice at a time
+        uint256 displayQuantity;
+        // What is left of the slice currently shown. A new slice loses its time priority
+        uint256 displayedAmount;
         uint256 timestamp_matched;
         bool isPartiallyFilled;
-        bool isFilled;                
+        bool isFilled;
+        // Set when the order will never rest in the book, e.g. the unfilled part of an IOC order
+        // or an expired order
+        bool isCancelled;
+        // Set once the trigger has fired, on the dormant order and on the live copy replacing it
+        bool isTriggered;
     }
 
     struct Task {
@@ -57,14 +94,50 @@ interface IOrderBookTaskManager {
         Order newOtherOrder;
     }
 
+    // A single fill of the incoming order against a resting order in the orderbook.
+    struct Fill {
+        uint256 orderIndex;
+        Order newOrder;
+        // Maker fee charged to the resting order, in the token it receives. Always 0 for
+        // self-trade updates.
+        uint256 makerFee;
+    }
+
     // Task response is hashed and signed by operators.
     // these signatures are aggregated and sent to the contract as response.
     struct TaskResponse {
         // Can be obtained by the operator from the event NewTaskCreated.
         uint32 referenceTaskIndex;
+        // 0 = resting (nothing crossed), 1 = matched, 2 = cancelled without any fill,
+        // 3 = rejected by the market specification
+        uint8 outcome;
+        // 0 unless rejected: 1 = below min size, 2 = invalid lot size, 3 = invalid tick size,
+        // 4 = below min notional
+        uint8 rejectionReason;
         Order newOrder;
-        Order newOtherOrder;
-        uint256 matchedOrderIndex;
+        // Every resting order filled against the incoming order, applied atomically.
+        Fill[] fills;
+        // Taker fee charged to the incoming order, in its token_not_owned.
+        uint256 takerFee;
+        // 0 if self-trade prevention was not triggered, otherwise the mode applied:
+        // 1 = cancel newest, 2 = cancel oldest, 3 = cancel both, 4 = decrement and cancel
+        uint8 selfTradeOutcome;
+        // Resting orders of the same user cancelled or decremented by self-trade prevention.
+        Fill[] selfTradeUpdates;
+        // Resting orders in other markets filled through rings with the incoming order,
+        // in settlement order. Ring fills are not charged fees.
+        Fill[] ringUpdates;
+        // True if the response settles the batch auction of a market's block window. The
+        // referenced task is the last one of the window and fills cover every order the
+        // auction changed, on both sides of the market.
+        bool isBatch;
+        // Uniform price of a batch auction in token1 per token0 base unit, 0/0 if nothing
+        // traded or the response is not a batch.
+        uint256 clearingPriceNumerator;
+        uint256 clearingPriceDenominator;
+        // Every other task of the batch window, answered by this response together with the
+        // referenced task. Empty if the response is not a batch.
+        uint32[] batchTasks;
     }
 
     // Extra information related to taskResponse, which is filled inside the contract.
@@ -83,10 +156,32 @@ interface IOrderBookTaskManager {
         address token_not_owned,
         address token_owned,
         uint256 slippage,
+        uint8 orderType,
+        uint256 expiry,
+        uint8 triggerType,
+        uint256 triggerPriceNumerator,
+        uint256 triggerPriceDenominator,
+        uint256 displayQuantity,
         uint32 quorumThresholdPercentage,
         bytes calldata quorumNumbers
     ) external;
 
+    // NOTE: this function replaces dormant orders whose trigger has fired with live copies.
+    function triggerOrders(
+        uint256[] calldata orderIndices,
+        uint32 quorumThresholdPercentage,
+        bytes calldata quorumNumbers
+    ) external;
+
+    // NOTE: this function cancels resting orders whose expiry has passed.
+    function expireOrders(uint256[] calldata orderIndices) external;
+
+    // NOTE: this function cancels an order of the caller that is still live.
+    function cancelOrder(uint256 orderIndex) external;
+
+    // NOTE: this function replaces the remaining amounts of an order of the caller.
+    function amendOrder(uint256 orderIndex, uint256 amount_owned, uint256 amount_not_owned) external;
+
     /// @notice Returns the current 'taskNumber' for the middleware
     function taskNumber() external view returns (uint32);
 
@@ -31,6 +31,16 @@ contract OrderBookTaskManager is
     uint32 public immutable TASK_RESPONSE_WINDOW_BLOCK;
     uint32 public constant TASK_CHALLENGE_WINDOW_BLOCK = 100;
     uint256 internal constant _THRESHOLD_DENOMINATOR = 100;
+    uint8 public constant MAX_ORDER_TYPE = 4;
+    uint8 public constant MAX_TRIGGER_TYPE = 2;
+    uint8 public constant TRIGGER_TYPE_STOP = 1;
+    uint8 public constant ORDER_TYPE_LIMIT = 0;
+    uint8 public constant ORDER_TYPE_FILL_OR_KILL = 3;
+    uint8 public constant ORDER_TYPE_POST_ONLY = 4;
+    uint8 public constant OUTCOME_RESTING = 0;
+    uint8 public constant OUTCOME_MATCHED = 1;
+    uint8 public constant OUTCOME_CANCELLED = 2;
+    uint8 public constant OUTCOME_REJECTED = 3;
 
     /* STORAGE */
     // The latest task index
@@ -50,6 +60,24 @@ contract OrderBookTaskManager is
     // Order book
     Order[] public orders;
 
+    // Block at which each order was last amended, cancelled or changed by a response, 0 if
+    // it never changed since it was created. Responses drop fills on orders changed from
+    // their task's block on, operators leave those orders out of the book they match.
+    uint32[] public orderChangedBlocks;
+
+    // Maker and taker fees collected from matched orders, per token
+    mapping(address => uint256) public collectedFees;
+
+    // Price in token1 per token0 of a market's last trade, 0/0 if it never traded
+    struct TradedPrice {
+        uint256 numerator;
+        uint256 denominator;
+    }
+
+    // Last traded price of each market, keyed by _marketKey. triggerOrders only fires
+    // dormant orders this price has crossed
+    mapping(bytes32 => TradedPrice) public lastTradedPrices;
+
     address public aggregator;
     address public generator;
 
@@ -95,9 +123,27 @@ contract OrderBookTaskManager is
         address token_not_owned,
         address token_owned,
         uint256 slippage,
+        uint8 orderType,
+        uint256 expiry,
+        uint8 triggerType,
+        uint256 triggerPriceNumerator,
+        uint256 triggerPriceDenominator,
+        uint256 displayQuantity,
         uint32 quorumThresholdPercentage,
         bytes calldata quorumNumbers
     ) external onlyTaskGenerator {
+        require(orderType <= MAX_ORDER_TYPE, "Invalid order type");
+        require(expiry == 0 || expiry > block.timestamp, "Order is already expired");
+        require(triggerType <= MAX_TRIGGER_TYPE, "Invalid trigger type");
+        require(
+            triggerType == 0 || (triggerPriceNumerator > 0 && triggerPriceDenominator > 0),
+            "A trigger order needs a trigger price"
+        );
+        // Only orders that rest in the book can hide part of their size
+        require(
+            displayQuantity == 0 || orderType == ORDER_TYPE_LIMIT || orderType == ORDER_TYPE_POST_ONLY,
+            "Only resting order types can be icebergs"
+        );
         // Check if the order is valid
         //require(price > 0, "Price must be greater than 0");
         // require(token != address(0), "Token must not be the zero address");        
@@ -113,10 +159,28 @@ contract OrderBookTaskManager is
         newOrder.token_not_owned = token_not_owned;
         newOrder.token_owned = token_owned;
         newOrder.slippage = slippage;
+        newOrder.orderType = orderType;
         newOrder.timestamp = block.timestamp;
+        newOrder.expiry = expiry;
+        newOrder.triggerType = triggerType;
+        newOrder.triggerPriceNumerator = triggerPriceNumerator;
+        newOrder.triggerPriceDenominator = triggerPriceDenominator;
+        newOrder.displayQuantity = displayQuantity;
+        newOrder.displayedAmount = displayQuantity < amount_owned ? displayQuantity : amount_owned;
         newOrder.isPartiallyFilled = false;
         newOrder.isFilled = false;
+        newOrder.isCancelled = false;
+        newOrder.isTriggered = false;
+
+        _createTask(newOrder, quorumThresholdPercentage, quorumNumbers);
+    }
 
+    // Pushes an incoming order to the order book and creates the task matching it.
+    function _createTask(
+        Order memory newOrder,
+        uint32 quorumThresholdPercentage,
+        bytes calldata quorumNumbers
+    ) internal {
         // Create a new order task
         Task memory newTask;
         newTask.order = newOrder;
@@ -127,6 +191,7 @@ contract OrderBookTaskManager is
 
         // Add the order to the order book
         orders.push(newOrder);
+        orderChangedBlocks.push(0);
 
         // Store task hash and emit event
         allTaskHashes[latestTaskNum] = keccak256(abi.encode(newTask));
@@ -134,6 +199,258 @@ contract OrderBookTaskManager is
         latestTaskNum = latestTaskNum + 1;
     }
 
+    // An order with a trigger that has not fired yet is never matched
+    function _isDormant(Order storage order) internal view returns (bool) {
+        return order.triggerType != 0 && !order.isTriggered;
+    }
+
+    // Only the displayed slice of a resting iceberg order can trade, its reserve waits for
+    // the next slice
+    function _withinDisplayedSlice(Order storage order, Order calldata newOrder) internal view returns (bool) {
+        return order.displayQuantity == 0 || order.amount_owned - newOrder.amount_owned <= order.displayedAmount;
+    }
+
+    // NOTE: this function checks that the operators who signed the task response hold at least
+    // the task's threshold percentage of the stake of every quorum, at the block the task was created.
+    function _checkQuorum(
+        Task calldata task,
+        TaskResponse calldata taskResponse,
+        NonSignerStakesAndSignature memory nonSignerStakesAndSignature
+    ) internal view returns (bytes32) {
+        (QuorumStakeTotals memory quorumStakeTotals, bytes32 hashOfNonSigners) = checkSignatures(
+            keccak256(abi.encode(taskResponse)),
+            task.quorumNumbers,
+            task.taskCreatedBlock,
+            nonSignerStakesAndSignature
+        );
+        for (uint256 i = 0; i < task.quorumNumbers.length; i++) {
+            require(
+                quorumStakeTotals.signedStakeForQuorum[i] * _THRESHOLD_DENOMINATOR >=
+                    quorumStakeTotals.totalStakeForQuorum[i] * uint256(task.quorumThresholdPercentage),
+                "Signatories do not own at least threshold percentage of a quorum"
+            );
+        }
+        return hashOfNonSigners;
+    }
+
+    function _markChanged(uint256 orderIndex) internal {
+        orderChangedBlocks[orderIndex] = uint32(block.number);
+    }
+
+    // NOTE: an order is stale for a task if it changed from the task's block on. Operators
+    // read the book at that block and leave such orders out, so any fill on a stale order
+    // was computed from an order that no longer exists.
+    function _isStale(Task calldata task, uint256 orderIndex) internal view returns (bool) {
+        return orderChangedBlocks[orderIndex] >= task.taskCreatedBlock;
+    }
+
+    // The full 512-bit product of a and b, as its high and low words
+    function _mul512(uint256 a, uint256 b) internal pure returns (uint256 high, uint256 low) {
+        unchecked {
+            low = a * b;
+            uint256 mm = mulmod(a, b, type(uint256).max);
+            high = mm - low - (mm < low ? 1 : 0);
+        }
+    }
+
+    // Whether a times b equals c times d, without overflowing
+    function _productsEqual(uint256 a, uint256 b, uint256 c, uint256 d) internal pure returns (bool) {
+        (uint256 high, uint256 low) = _mul512(a, b);
+        (uint256 otherHigh, uint256 otherLow) = _mul512(c, d);
+        return high == otherHigh && low == otherLow;
+    }
+
+    // Whether a times b is at least c times d, without overflowing
+    function _productAtLeast(uint256 a, uint256 b, uint256 c, uint256 d) internal pure returns (bool) {
+        (uint256 high, uint256 low) = _mul512(a, b);
+        (uint256 otherHigh, uint256 otherLow) = _mul512(c, d);
+        return high > otherHigh || (high == otherHigh && low >= otherLow);
+    }
+
+    // Markets are keyed by their two tokens in ascending address order, token0 first
+    function _marketKey(address tokenA, address tokenB) internal pure returns (bytes32) {
+        return tokenA < tokenB ? keccak256(abi.encode(tokenA, tokenB)) : keccak256(abi.encode(tokenB, tokenA));
+    }
+
+    // Records the price a resting order traded at before newOrder replaces it. An ask
+    // gives token0 and a bid gives token1.
+    function _recordTrade(Order storage resting, Order calldata newOrder) internal {
+        uint256 given = resting.amount_owned - newOrder.amount_owned;
+        uint256 received = resting.amount_not_owned - newOrder.amount_not_owned;
+        if (given == 0 || received == 0) {
+            return;
+        }
+        TradedPrice storage last = lastTradedPrices[_marketKey(resting.token_owned, resting.token_not_owned)];
+        if (resting.token_owned < resting.token_not_owned) {
+            (last.numerator, last.denominator) = (received, given);
+        } else {
+            (last.numerator, last.denominator) = (given, received);
+        }
+    }
+
+    // Whether the last traded price of a dormant order's market crossed its trigger. Stop
+    // bids and take-profit asks fire once the price rises to the trigger, stop asks and
+    // take-profit bids once it falls to it.
+    function _isCrossed(Order storage order) internal view returns (bool) {
+        TradedPrice storage last = lastTradedPrices[_marketKey(order.token_owned, order.token_not_owned)];
+        if (last.denominator == 0) {
+            return false;
+        }
+        bool isBid = order.token_owned > order.token_not_owned;
+        if ((order.triggerType == TRIGGER_TYPE_STOP) == isBid) {
+            return _productAtLeast(
+                last.numerator, order.triggerPriceDenominator, order.triggerPriceNumerator, last.denominator
+            );
+        }
+        return _productAtLeast(
+            order.triggerPriceNumerator, last.denominator, last.numerator, order.triggerPriceDenominator
+        );
+    }
+
+    function _touchesStale(Task calldata task, Fill[] calldata updates) internal view returns (bool) {
+        for (uint256 i = 0; i < updates.length; i++) {
+            if (_isStale(task, updates[i].orderIndex)) {
+                return true;
+            }
+        }
+        return false;
+    }
+
+    // NOTE: this function applies a response to the order book. A fill on a stale order is
+    // dropped and what the incoming order would have traded with it is handed back, so one
+    // owner changing an order cannot hold up the tasks of other users.
+    function _settle(Task calldata task, TaskResponse calldata taskResponse) internal {
+        Order storage order = orders[taskResponse.referenceTaskIndex];
+
+        // A resting order is acknowledged as is, unless self-trade prevention touched it
+        if (taskResponse.outcome == OUTCOME_RESTING && taskResponse.selfTradeOutcome == 0) {
+            require(
+                keccak256(abi.encode(taskResponse.newOrder)) == keccak256(abi.encode(order)),
+                "A resting order must be left unchanged"
+            );
+        }
+        // Dropping part of an auction would unbalance it, the batch is voided instead and
+        // its orders are cleared with the next window
+        if (taskResponse.isBatch && _touchesStale(task, taskResponse.fills)) {
+            return;
+        }
+        bool dropRings = _touchesStale(task, taskResponse.ringUpdates);
+        if (
+            order.orderType == ORDER_TYPE_FILL_OR_KILL
+                && (dropRings || _touchesStale(task, taskResponse.fills))
+        ) {
+            // A fill-or-kill order cannot be filled in part
+            order.isCancelled = true;
+            _markChanged(taskResponse.referenceTaskIndex);
+            return;
+        }
+
+        Order memory newOrder = taskResponse.newOrder;
+        uint256 received = 0;
+        uint256 dropped = 0;
+        for (uint256 i = 0; i < taskResponse.fills.length; i++) {
+            Fill calldata fill = taskResponse.fills[i];
+            require(
+                fill.orderIndex < taskResponse.referenceTaskIndex,
+                "Fill must reference a resting order"
+            );
+            if (_isStale(task, fill.orderIndex)) {
+                // The fill was computed from the order as the task's book held it
+                Order calldata seen = task.orderbook[fill.orderIndex];
+                uint256 given = seen.amount_owned - fill.newOrder.amount_owned;
+                newOrder.amount_not_owned += given;
+                newOrder.amount_owned += seen.amount_not_owned - fill.newOrder.amount_not_owned;
+                dropped += given;
+                continue;
+            }
+            Order storage resting = orders[fill.orderIndex];
+            uint256 amount = resting.amount_owned - fill.newOrder.amount_owned;
+            require(!resting.isCancelled, "Fill references a cancelled order");
+            require(!_isDormant(resting), "Fill references a dormant order");
+            require(
+                _withinDisplayedSlice(resting, fill.newOrder),
+                "Fill exceeds the displayed slice of an iceberg order"
+            );
+            require(
+                fill.makerFee <= resting.amount_not_owned - fill.newOrder.amount_not_owned,
+                "Maker fee exceeds the amount received"
+            );
+            received += amount;
+            collectedFees[order.token_owned] += fill.makerFee;
+            // A batch trades at its uniform clearing price, recorded below
+            if (!taskResponse.isBatch) {
+                _recordTrade(resting, fill.newOrder);
+            }
+            orders[fill.orderIndex] = fill.newOrder;
+            _markChanged(fill.orderIndex);
+        }
+
+        // Rings are only formed without direct fills or self-trade prevention, so dropping
+        // them leaves the incoming order as it arrived
+        if (dropRings) {
+            newOrder.amount_owned = order.amount_owned;
+            newOrder.amount_not_owned = order.amount_not_owned;
+        }
+        uint256 takerFee = taskResponse.takerFee;
+        if (dropped > 0 || dropRings) {
+            // The taker fee is charged on what was actually received
+            takerFee = dropped > 0 ? takerFee * received / (received + dropped) : takerFee;
+            newOrder.isFilled = false;
+            newOrder.isPartiallyFilled = received > 0;
+            if (newOrder.orderType != ORDER_TYPE_LIMIT && newOrder.orderType != ORDER_TYPE_POST_ONLY) {
+                newOrder.isCancelled = true;
+            }
+        }
+        require(takerFee <= received, "Taker fee exceeds the amount received");
+        collectedFees[order.token_not_owned] += takerFee;
+
+        for (uint256 i = 0; i < taskResponse.selfTradeUpdates.length; i++) {
+            Fill calldata update = taskResponse.selfTradeUpdates[i];
+            require(
+                update.orderIndex < taskResponse.referenceTaskIndex,
+                "Self-trade update must reference a resting order"
+            );
+            if (_isStale(task, update.orderIndex)) {
+                continue;
+            }
+            require(
+                orders[update.orderIndex].user == order.user,
+                "Self-trade update must reference an order of the same user"
+            );
+            require(update.makerFee == 0, "Self-trade updates are not charged fees");
+            orders[update.orderIndex] = update.newOrder;
+            _markChanged(update.orderIndex);
+        }
+
+        for (uint256 i = 0; i < taskResponse.ringUpdates.length && !dropRings; i++) {
+            Fill calldata update = taskResponse.ringUpdates[i];
+            require(
+                update.orderIndex < taskResponse.referenceTaskIndex,
+                "Ring fill must reference a resting order"
+            );
+            require(!orders[update.orderIndex].isCancelled, "Ring fill references a cancelled order");
+            require(!_isDormant(orders[update.orderIndex]), "Ring fill references a dormant order");
+            require(
+                _withinDisplayedSlice(orders[update.orderIndex], update.newOrder),
+                "Ring fill exceeds the displayed slice of an iceberg order"
+            );
+            require(update.makerFee == 0, "Ring fills are not charged fees");
+            _recordTrade(orders[update.orderIndex], update.newOrder);
+            orders[update.orderIndex] = update.newOrder;
+            _markChanged(update.orderIndex);
+        }
+
+        if (taskResponse.isBatch && taskResponse.clearingPriceDenominator != 0) {
+            lastTradedPrices[_marketKey(order.token_owned, order.token_not_owned)] = TradedPrice(
+                taskResponse.clearingPriceNumerator,
+                taskResponse.clearingPriceDenominator
+            );
+        }
+
+        orders[taskResponse.referenceTaskIndex] = newOrder;
+        _markChanged(taskResponse.referenceTaskIndex);
+    }
+
     // NOTE: this function responds to existing tasks.
     function respondToTask(
         Task calldata task,
@@ -153,39 +470,192 @@ contract OrderBookTaskManager is
             allTaskResponses[taskResponse.referenceTaskIndex] == bytes32(0),
             "Aggregator has already responded to the task"
         );
+        // a batch is settled once its window has closed, however long the window is
         require(
-            uint32(block.number) <=
+            taskResponse.isBatch || uint32(block.number) <=
                 taskCreatedBlock + TASK_RESPONSE_WINDOW_BLOCK,
             "Aggregator has responded to the task too late"
         );
-        
-        uint256 newOrderTransferAmt = orders[orders.length - 1].amount_owned - taskResponse.newOrder.amount_owned;
-        uint256 newOrderRewardAmt = taskResponse.newOrder.amount_not_owned - orders[orders.length - 1].amount_not_owned;
-
-        uint256 newOtherOrderTransferAmt = orders[taskResponse.referenceTaskIndex].amount_owned - taskResponse.newOtherOrder.amount_owned;
-        uint256 newOtherOrderRewardAmt = taskResponse.newOtherOrder.amount_not_owned - orders[taskResponse.referenceTaskIndex].amount_not_owned;
-
-        uint256 rewardTokenAmtFromOtherOrder = newOtherOrderTransferAmt - newOrderRewardAmt;    
+        // the response must be signed by the task's quorums before anything is applied
+        bytes32 hashOfNonSigners = _checkQuorum(task, taskResponse, nonSignerStakesAndSignature);
 
-        //update orders in orderbook
+        require(taskResponse.outcome <= OUTCOME_REJECTED, "Invalid outcome");
+        require(
+            (taskResponse.outcome == OUTCOME_REJECTED) == (taskResponse.rejectionReason != 0),
+            "Only a rejected response has a rejection reason"
+        );
+        require(
+            taskResponse.outcome != OUTCOME_REJECTED || taskResponse.newOrder.isCancelled,
+            "A rejected order must not rest in the book"
+        );
+        // A batch settles every order of its window, so fills may also cancel orders
+        // that could not rest without the batch trading anything
+        require(
+            taskResponse.outcome != OUTCOME_MATCHED
+                || taskResponse.fills.length > 0
+                || taskResponse.ringUpdates.length > 0,
+            "A matched response must contain fills"
+        );
+        require(
+            taskResponse.isBatch || taskResponse.outcome == OUTCOME_MATCHED || taskResponse.fills.length == 0,
+            "Only a matched response can contain fills"
+        );
+        require(
+            taskResponse.outcome == OUTCOME_MATCHED || taskResponse.ringUpdates.length == 0,
+            "Only a matched response can contain ring fills"
+        );
+        require(
+            taskResponse.isBatch || taskResponse.clearingPriceDenominator == 0,
+            "Only a batch response has a clearing price"
+        );
+        require(
+            !taskResponse.isBatch || taskResponse.takerFee == 0,
+            "Batch auctions have no taker"
+        );
+        require(
+            taskResponse.isBatch || taskResponse.batchTasks.length == 0,
+            "Only a batch response answers other tasks"
+        );
+        for (uint256 i = 0; i < taskResponse.batchTasks.length; i++) {
+            require(
+                taskResponse.batchTasks[i] < taskResponse.referenceTaskIndex,
+                "A batch only answers earlier tasks of its window"
+            );
+            require(
+                allTaskResponses[taskResponse.batchTasks[i]] == bytes32(0),
+                "Aggregator has already responded to a task of the batch"
+            );
+        }
 
-        orders[taskResponse.referenceTaskIndex] = taskResponse.newOtherOrder;
-        orders[orders.length - 1] = taskResponse.newOrder;
+        // The owner may have cancelled the order while its task was pending, or another
+        // response filled it. Nothing is traded for it then, the task is only answered
+        if (!_isStale(task, taskResponse.referenceTaskIndex)) {
+            _settle(task, taskResponse);
+        }
 
         TaskResponseMetadata memory taskResponseMetadata = TaskResponseMetadata(
             uint32(block.number),
-            bytes32(0)
+            hashOfNonSigners
         );
 
         // updating the storage with task response
         allTaskResponses[taskResponse.referenceTaskIndex] = keccak256(
             abi.encode(taskResponse, taskResponseMetadata)
         );
+        // the other tasks of a batch window are answered by the same response
+        for (uint256 i = 0; i < taskResponse.batchTasks.length; i++) {
+            allTaskResponses[taskResponse.batchTasks[i]] = allTaskResponses[taskResponse.referenceTaskIndex];
+        }
 
         // emitting event
         emit TaskResponded(taskResponse, taskResponseMetadata);
     }
 
+    // NOTE: this function cancels resting orders whose expiry has passed.
+    // Orders that are not expired yet, or are no longer resting, are skipped.
+    function expireOrders(uint256[] calldata orderIndices) external onlyAggregator {
+        for (uint256 i = 0; i < orderIndices.length; i++) {
+            uint256 orderIndex = orderIndices[i];
+            require(orderIndex < orders.length, "Order does not exist");
+
+            Order storage order = orders[orderIndex];
+            if (order.expiry == 0 || order.expiry > block.timestamp || order.isFilled || order.isCancelled) {
+                continue;
+            }
+
+            order.isCancelled = true;
+            _markChanged(orderIndex);
+            emit OrderExpired(orderIndex);
+        }
+    }
+
+    // NOTE: this function replaces dormant orders whose trigger has fired with live copies.
+    // Operators derive the last traded price of each market from TaskResponded events and
+    // the aggregator submits the orders it crossed. Each dormant order is retired and a live
+    // copy is pushed as a new task, so it is matched as an incoming order with a fresh time
+    // priority. Orders that are not dormant, whose own task has not been answered yet, or
+    // whose trigger the last price recorded by respondToTask has not crossed, are skipped.
+    function triggerOrders(
+        uint256[] calldata orderIndices,
+        uint32 quorumThresholdPercentage,
+        bytes calldata quorumNumbers
+    ) external onlyAggregator {
+        for (uint256 i = 0; i < orderIndices.length; i++) {
+            uint256 orderIndex = orderIndices[i];
+            require(orderIndex < orders.length, "Order does not exist");
+
+            Order storage order = orders[orderIndex];
+            if (
+                !_isDormant(order)
+                    || order.isFilled
+                    || order.isCancelled
+                    || (order.expiry != 0 && order.expiry <= block.timestamp)
+                    || allTaskResponses[uint32(orderIndex)] == bytes32(0)
+                    || !_isCrossed(order)
+            ) {
+                continue;
+            }
+
+            order.isTriggered = true;
+            order.isCancelled = true;
+            _markChanged(orderIndex);
+
+            Order memory liveOrder = order;
+            liveOrder.timestamp = block.timestamp;
+            liveOrder.isCancelled = false;
+
+            emit OrderTriggered(orderIndex, latestTaskNum);
+            _createTask(liveOrder, quorumThresholdPercentage, quorumNumbers);
+        }
+    }
+
+    // NOTE: this function cancels an order of the caller that is still live.
+    // Tasks are matched against the orders held at their block, so tasks created from
+    // here on no longer match the order, and fills on it in pending responses are dropped.
+    function cancelOrder(uint256 orderIndex) external {
+        require(orderIndex < orders.length, "Order does not exist");
+
+        Order storage order = orders[orderIndex];
+        require(order.user == msg.sender, "Only the owner can cancel an order");
+        require(!order.isFilled && !order.isCancelled, "Order is no longer live");
+
+        order.isCancelled = true;
+        _markChanged(orderIndex);
+        emit OrderCancelled(orderIndex, msg.sender);
+    }
+
+    // NOTE: this function replaces the remaining amounts of an order of the caller.
+    // Reducing the size at the same price keeps the order's time priority, any other
+    // amendment resets it as if the order had been cancelled and replaced. An order can
+    // only be amended once its own task is answered, fills computed before the amendment
+    // are dropped from pending responses.
+    function amendOrder(uint256 orderIndex, uint256 amount_owned, uint256 amount_not_owned) external {
+        require(orderIndex < orders.length, "Order does not exist");
+        require(amount_owned > 0 && amount_not_owned > 0, "Use cancelOrder to remove an order");
+
+        Order storage order = orders[orderIndex];
+        require(order.user == msg.sender, "Only the owner can amend an order");
+        require(!order.isFilled && !order.isCancelled, "Order is no longer live");
+        require(allTaskResponses[uint32(orderIndex)] != bytes32(0), "The order's task has not been answered yet");
+
+        bool samePrice = _productsEqual(amount_owned, order.amount_not_owned, amount_not_owned, order.amount_owned);
+        bool keepsPriority = samePrice && amount_owned <= order.amount_owned;
+        if (!keepsPriority) {
+            order.timestamp = block.timestamp;
+        }
+        // An iceberg keeps what is left of its slice, or shows a fresh one once its
+        // priority is reset
+        if (order.displayQuantity != 0) {
+            uint256 slice = keepsPriority ? order.displayedAmount : order.displayQuantity;
+            order.displayedAmount = slice < amount_owned ? slice : amount_owned;
+        }
+        order.amount_owned = amount_owned;
+        order.amount_not_owned = amount_not_owned;
+        _markChanged(orderIndex);
+
+        emit OrderAmended(orderIndex, msg.sender, amount_owned, amount_not_owned, block.timestamp, latestTaskNum);
+    }
+
     function taskNumber() external view returns (uint32) {
         return latestTaskNum;
     }
@@ -283,4 +753,25 @@ contract OrderBookTaskManager is
     function getAllOrders() external view returns (Order[] memory) {
         return orders;
     }
+
+    // NOTE: this function returns the order book as shown to traders: an iceberg order only
+    // shows its displayed slice, asking for the slice's share of amount_not_owned rounded up.
+    // Operators match the full orders, so `orders`, getAllOrders and NewTaskCreated still
+    // carry the reserve. Hiding it from anyone reading the chain is out of scope.
+    function getDisplayedOrders() external view returns (Order[] memory displayed) {
+        displayed = orders;
+        for (uint256 i = 0; i < displayed.length; i++) {
+            Order memory order = displayed[i];
+            if (order.displayQuantity == 0 || order.displayedAmount >= order.amount_owned) {
+                continue;
+            }
+            order.amount_not_owned =
+                (order.amount_not_owned * order.displayedAmount + order.amount_owned - 1) / order.amount_owned;
+            order.amount_owned = order.displayedAmount;
+        }
+    }
+
+    function getOrderChangedBlocks() external view returns (uint32[] memory) {
+        return orderChangedBlocks;
+    }
 }
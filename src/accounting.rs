@@ -0,0 +1,148 @@
+use crate::matching::engine::MatchResult;
+use crate::matching::market::Market;
+use crate::IOrderBookTaskManager::Order;
+use alloy_primitives::{Address, U256};
+use std::collections::{BTreeMap, HashMap};
+
+/// Fee totals keyed by the token the fees were paid in.
+pub type FeeTotals = HashMap<Address, U256>;
+
+/// Epoch a task belongs to, given the block it was created in.
+pub fn epoch_of(task_created_block: u32, epoch_blocks: u32) -> u64 {
+    u64::from(task_created_block) / u64::from(epoch_blocks.max(1))
+}
+
+/// Running totals of the fees charged in the tasks this operator signed.
+///
+/// Fees are paid in different tokens, so every total is kept per token, both per
+/// market and per epoch.
+#[derive(Debug, Clone, Default)]
+pub struct FeeLedger {
+    by_market: HashMap<Market, FeeTotals>,
+    by_epoch: BTreeMap<u64, FeeTotals>,
+}
+
+impl FeeLedger {
+    /// Records the maker and taker fees of every fill of `result`.
+    pub fn record(&mut self, order: &Order, result: &MatchResult, epoch: u64) {
+        let Some(market) = Market::of(order) else {
+            return;
+        };
+
+        // Resting orders receive the incoming order's `token_owned` and the incoming
+        // order receives its `token_not_owned`
+        let maker_fee = result
+            .fills
+            .iter()
+            .fold(U256::ZERO, |sum, fill| sum.saturating_add(fill.maker_fee));
+        let fees = [
+            (order.token_owned, maker_fee),
+            (order.token_not_owned, result.taker_fee()),
+        ];
+
+        for (token, amount) in fees {
+            if amount.is_zero() {
+                continue;
+            }
+            for totals in [
+                self.by_market.entry(market).or_default(),
+                self.by_epoch.entry(epoch).or_default(),
+            ] {
+                let total = totals.entry(token).or_default();
+                *total = total.saturating_add(amount);
+            }
+        }
+    }
+
+    pub fn market_totals(&self, market: &Market) -> Option<&FeeTotals> {
+        self.by_market.get(market)
+    }
+
+    pub fn epoch_totals(&self, epoch: u64) -> Option<&FeeTotals> {
+        self.by_epoch.get(&epoch)
+    }
+
+    /// Every epoch with recorded fees, oldest first.
+    pub fn epochs(&self) -> impl Iterator<Item = (&u64, &FeeTotals)> {
+        self.by_epoch.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::engine::{match_order, MatchingConfig};
+    use crate::matching::fees::FeeSchedule;
+    use crate::matching::test_support::{self, order, DAI, USDC, WETH};
+
+    fn config() -> MatchingConfig {
+        MatchingConfig {
+            fees: FeeSchedule::new(10, 20).unwrap(),
+            ..test_support::config()
+        }
+    }
+
+    fn record(ledger: &mut FeeLedger, incoming: &Order, resting: &Order, epoch: u64) {
+        let result = match_order(incoming, &[resting.clone()], &config()).unwrap();
+        ledger.record(incoming, &result, epoch);
+    }
+
+    #[test]
+    fn test_epoch_of() {
+        assert_eq!(epoch_of(0, 100), 0);
+        assert_eq!(epoch_of(99, 100), 0);
+        assert_eq!(epoch_of(100, 100), 1);
+        assert_eq!(epoch_of(5, 0), 5);
+    }
+
+    #[test]
+    fn test_totals_per_market_and_epoch() {
+        let mut ledger = FeeLedger::default();
+        record(
+            &mut ledger,
+            &order(1, WETH, 10_000, USDC, 10_000, 5),
+            &order(2, USDC, 10_000, WETH, 10_000, 5),
+            0,
+        );
+        record(
+            &mut ledger,
+            &order(3, USDC, 10_000, WETH, 10_000, 5),
+            &order(4, WETH, 10_000, USDC, 10_000, 5),
+            1,
+        );
+        record(
+            &mut ledger,
+            &order(5, DAI, 10_000, WETH, 10_000, 5),
+            &order(6, WETH, 10_000, DAI, 10_000, 5),
+            1,
+        );
+
+        let weth_usdc = ledger
+            .market_totals(&Market::new(WETH, USDC).unwrap())
+            .unwrap();
+        assert_eq!(weth_usdc[&WETH], U256::from(30));
+        assert_eq!(weth_usdc[&USDC], U256::from(30));
+
+        let epoch_0 = ledger.epoch_totals(0).unwrap();
+        assert_eq!(epoch_0[&WETH], U256::from(10));
+        assert_eq!(epoch_0[&USDC], U256::from(20));
+
+        let epoch_1 = ledger.epoch_totals(1).unwrap();
+        assert_eq!(epoch_1[&WETH], U256::from(40));
+        assert_eq!(epoch_1[&USDC], U256::from(10));
+        assert_eq!(epoch_1[&DAI], U256::from(10));
+        assert_eq!(ledger.epochs().count(), 2);
+    }
+
+    #[test]
+    fn test_unmatched_orders_record_nothing() {
+        let mut ledger = FeeLedger::default();
+        let incoming = order(1, WETH, 10_000, USDC, 10_000, 5);
+        ledger.record(
+            &incoming,
+            &match_order(&incoming, &[], &config()).unwrap(),
+            0,
+        );
+        assert!(ledger.epoch_totals(0).is_none());
+    }
+}
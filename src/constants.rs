@@ -1,4 +1,10 @@
-use alloy_primitives::{address, Address, U256};
+use crate::matching::auction::BatchMarkets;
+use crate::matching::spec::MarketSpecs;
+use crate::matching::stp::SelfTradePrevention;
+use crate::matching::strategy::StrategyKind;
+use crate::tokens::TokenRegistry;
+use crate::twap::TwapSpecs;
+use alloy_primitives::{address, Address, Bytes, U256};
 use lazy_static::lazy_static;
 use std::env;
 
@@ -18,7 +24,91 @@ lazy_static! {
     pub static ref TASK_MANAGER_ADDRESS: Address = env::var("TASK_MANAGER_ADDRESS")
         .map(|addr| addr.parse().expect("Invalid TASK_MANAGER_ADDRESS"))
         .unwrap_or_else(|_| address!("D0141E899a65C95a556fE2B27e5982A6DE7fDD7A"));
+    /// Maximum number of resting orders a single incoming order may be filled against.
+    pub static ref MAX_FILLS_PER_TASK: usize = env::var("MAX_FILLS_PER_TASK")
+        .map(|max| max.parse().expect("Invalid MAX_FILLS_PER_TASK"))
+        .unwrap_or(16);
+    /// Matching strategy used by this deployment: `fifo`, `pro-rata` or `midpoint`.
+    pub static ref MATCHING_STRATEGY: StrategyKind = env::var("MATCHING_STRATEGY")
+        .map(|strategy| strategy.parse().expect("Invalid MATCHING_STRATEGY"))
+        .unwrap_or_default();
+    /// Self-trade prevention mode: `cancel-newest`, `cancel-oldest`, `cancel-both` or
+    /// `decrement-and-cancel`.
+    pub static ref SELF_TRADE_PREVENTION: SelfTradePrevention = env::var("SELF_TRADE_PREVENTION")
+        .map(|mode| mode.parse().expect("Invalid SELF_TRADE_PREVENTION"))
+        .unwrap_or_default();
+    /// Fee charged to resting orders, in basis points of what they receive.
+    pub static ref MAKER_FEE_BPS: u32 = env::var("MAKER_FEE_BPS")
+        .map(|bps| bps.parse().expect("Invalid MAKER_FEE_BPS"))
+        .unwrap_or(0);
+    /// Fee charged to incoming orders, in basis points of what they receive.
+    pub static ref TAKER_FEE_BPS: u32 = env::var("TAKER_FEE_BPS")
+        .map(|bps| bps.parse().expect("Invalid TAKER_FEE_BPS"))
+        .unwrap_or(0);
+    /// Known tokens as a comma separated list of `address:symbol:decimals`, other tokens
+    /// are resolved from their contract.
+    pub static ref TOKEN_METADATA: TokenRegistry = env::var("TOKEN_METADATA")
+        .unwrap_or_else(|_| {
+            "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2:WETH:18,\
+             0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48:USDC:6"
+                .to_string()
+        })
+        .parse()
+        .expect("Invalid TOKEN_METADATA");
+    /// Granularity rules per market as a comma separated list of
+    /// `token_a:token_b:min_size:lot_size:tick_size:min_notional`, in base units.
+    pub static ref MARKET_SPECS: MarketSpecs = env::var("MARKET_SPECS")
+        .map(|specs| specs.parse().expect("Invalid MARKET_SPECS"))
+        .unwrap_or_default();
+    /// Markets matched in frequent batch auctions as a comma separated list of
+    /// `token_a:token_b`, every other market is matched continuously.
+    pub static ref BATCH_AUCTION_MARKETS: BatchMarkets = env::var("BATCH_AUCTION_MARKETS")
+        .map(|markets| markets.parse().expect("Invalid BATCH_AUCTION_MARKETS"))
+        .unwrap_or_default();
+    /// Number of blocks in a batch auction window.
+    pub static ref BATCH_WINDOW_BLOCKS: u32 = env::var("BATCH_WINDOW_BLOCKS")
+        .map(|blocks| blocks.parse().expect("Invalid BATCH_WINDOW_BLOCKS"))
+        .unwrap_or(5);
+    /// Local file the batch auctions waiting to be settled are persisted to.
+    pub static ref BATCH_STATE_PATH: String =
+        env::var("BATCH_STATE_PATH").unwrap_or_else(|_| "batch_state.json".to_string());
+    /// Maximum number of orders in a ring match, including the incoming order. Values
+    /// below three disable ring matching.
+    pub static ref MAX_RING_LENGTH: usize = env::var("MAX_RING_LENGTH")
+        .map(|length| length.parse().expect("Invalid MAX_RING_LENGTH"))
+        .unwrap_or(3);
+    /// Parent orders executed by the TWAP scheduler as a comma separated list of
+    /// `token_owned:token_not_owned:amount_owned:amount_not_owned:slices:horizon_secs`,
+    /// in base units. Only used when no state has been persisted yet.
+    pub static ref TWAP_ORDERS: TwapSpecs = env::var("TWAP_ORDERS")
+        .unwrap_or_else(|_| {
+            "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48:\
+             0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2:10000000:400000000000000000000:2:10"
+                .to_string()
+        })
+        .parse()
+        .expect("Invalid TWAP_ORDERS");
+    /// File the state of the TWAP scheduler's parent orders is persisted to.
+    pub static ref TWAP_STATE_PATH: String =
+        env::var("TWAP_STATE_PATH").unwrap_or_else(|_| "twap_state.json".to_string());
+    /// Quorums that must sign the responses to the tasks this deployment creates itself,
+    /// such as TWAP children, as a comma separated list of quorum numbers.
+    pub static ref TASK_QUORUM_NUMBERS: Bytes = env::var("TASK_QUORUM_NUMBERS")
+        .unwrap_or_else(|_| "0".to_string())
+        .split(',')
+        .map(|quorum| quorum.trim().parse::<u8>().expect("Invalid TASK_QUORUM_NUMBERS"))
+        .collect::<Vec<u8>>()
+        .into();
+    /// Percentage of the stake of each quorum that must sign those responses.
+    pub static ref TASK_QUORUM_THRESHOLD_PERCENTAGE: u32 =
+        env::var("TASK_QUORUM_THRESHOLD_PERCENTAGE")
+            .map(|threshold| threshold.parse().expect("Invalid TASK_QUORUM_THRESHOLD_PERCENTAGE"))
+            .unwrap_or(100);
+    /// Number of blocks in an operator fee accounting epoch.
+    pub static ref FEE_EPOCH_BLOCKS: u32 = env::var("FEE_EPOCH_BLOCKS")
+        .map(|blocks| blocks.parse().expect("Invalid FEE_EPOCH_BLOCKS"))
+        .unwrap_or(7200);
 }
 
 pub const OPERATOR_ADDRESS: Address = address!("f39fd6e51aad88f6f4ce6ab8827279cfffb92266");
-pub const OPERATOR_METADATA_URL: &str = "https://github.com/tangle-network/gadget";
\ No newline at end of file
+pub const OPERATOR_METADATA_URL: &str = "https://github.com/tangle-network/gadget";
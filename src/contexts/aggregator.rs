@@ -3,12 +3,19 @@ use crate::IOrderBookTaskManager::Task;
 use crate::IOrderBookTaskManager::TaskResponse;
 use crate::BN254::G1Point;
 use crate::BN254::G2Point;
+use crate::constants::TASK_QUORUM_NUMBERS;
+use crate::matching::engine::MatchOutcome;
+use crate::matching::expiry::expired_orders;
+use crate::matching::trigger::TriggerBook;
+use crate::quorum::{Progress, SignatureError, TaskSignatures};
 use crate::{contexts::client::SignedTaskResponse, Error, OrderBookTaskManager};
 use alloy_network::{Ethereum, NetworkWallet};
-use alloy_primitives::{keccak256, Address};
+use alloy_primitives::{keccak256, Address, U256};
+use alloy_provider::Provider;
 use alloy_sol_types::SolType;
-use jsonrpc_core::{IoHandler, Params, Value};
+use jsonrpc_core::{ErrorCode, IoHandler, Params, Value};
 use jsonrpc_http_server::{AccessControlAllowOrigin, DomainsValidation, ServerBuilder};
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{collections::VecDeque, net::SocketAddr, sync::Arc, time::Duration};
 use tokio::sync::{oneshot, Mutex, Notify};
 use tokio::task::JoinHandle;
@@ -24,17 +31,41 @@ use blueprint_sdk::runners::core::runner::BackgroundService;
 use blueprint_sdk::eigensdk::client_avsregistry::reader::AvsRegistryChainReader;
 use blueprint_sdk::eigensdk::common::get_provider;
 use blueprint_sdk::eigensdk::crypto_bls::{convert_to_g1_point, convert_to_g2_point, BlsG1Point, BlsG2Point};
+use blueprint_sdk::eigensdk::crypto_bn254::utils::verify_message;
 use blueprint_sdk::eigensdk::services_avsregistry::chaincaller::AvsRegistryServiceChainCaller;
+use blueprint_sdk::eigensdk::services_avsregistry::AvsRegistryService;
 use blueprint_sdk::eigensdk::services_blsaggregation::{
     bls_agg::BlsAggregatorService, bls_aggregation_service_response::BlsAggregationServiceResponse,
 };
 use blueprint_sdk::eigensdk::services_operatorsinfo::operatorsinfo_inmemory::OperatorInfoServiceInMemory;
 use blueprint_sdk::eigensdk::types::avs::{TaskIndex, TaskResponseDigest};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+const EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// JSON-RPC error codes returned for signed task responses rejected on ingest.
+const INVALID_SIGNATURE_ERROR: i64 = -32001;
+const UNKNOWN_OPERATOR_ERROR: i64 = -32002;
+const DUPLICATE_OPERATOR_ERROR: i64 = -32003;
+const CACHE_FULL_ERROR: i64 = -32004;
+const SKIPPED_TASK_ERROR: i64 = -32005;
+
+/// Responses waiting for their task to be initialized, past which new ones are rejected.
+const MAX_CACHED_RESPONSES: usize = 1024;
+
+/// Why a response to a task that is not initialized yet was not cached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum CacheError {
+    #[error("{0} responses are already waiting for their task to be initialized")]
+    Full(usize),
+    #[error("task {0} will not be initialized, its operators could not be read")]
+    SkippedTask(TaskIndex),
+}
+
+pub type AvsRegistryServiceInMemory =
+    AvsRegistryServiceChainCaller<AvsRegistryChainReader, OperatorInfoServiceInMemory>;
 
-pub type BlsAggServiceInMemory = BlsAggregatorService<
-    AvsRegistryServiceChainCaller<AvsRegistryChainReader, OperatorInfoServiceInMemory>,
->;
+pub type BlsAggServiceInMemory = BlsAggregatorService<AvsRegistryServiceInMemory>;
 
 #[derive(Clone, EigenlayerContext, KeystoreContext)]
 pub struct AggregatorContext {
@@ -42,10 +73,14 @@ pub struct AggregatorContext {
     pub task_manager_address: Address,
     pub tasks: Arc<Mutex<HashMap<TaskIndex, Task>>>,
     pub tasks_responses: Arc<Mutex<HashMap<TaskIndex, HashMap<TaskResponseDigest, TaskResponse>>>>,
+    pub signatures: Arc<Mutex<HashMap<TaskIndex, TaskSignatures>>>,
     pub bls_aggregation_service: Option<Arc<Mutex<BlsAggServiceInMemory>>>,
+    pub avs_registry_service: Option<Arc<AvsRegistryServiceInMemory>>,
     pub http_rpc_url: String,
     pub wallet: EthereumWallet,
     pub response_cache: Arc<Mutex<VecDeque<SignedTaskResponse>>>,
+    pub skipped_tasks: Arc<Mutex<HashSet<TaskIndex>>>,
+    pub triggers: Arc<Mutex<TriggerBook>>,
     #[config]
     pub sdk_config: GadgetConfiguration,
     shutdown: Arc<(Notify, Mutex<bool>)>,
@@ -63,10 +98,14 @@ impl AggregatorContext {
             task_manager_address,
             tasks: Arc::new(Mutex::new(HashMap::new())),
             tasks_responses: Arc::new(Mutex::new(HashMap::new())),
+            signatures: Arc::new(Mutex::new(HashMap::new())),
             bls_aggregation_service: None,
+            avs_registry_service: None,
             http_rpc_url: sdk_config.http_rpc_endpoint.clone(),
             wallet,
             response_cache: Arc::new(Mutex::new(VecDeque::new())),
+            skipped_tasks: Arc::new(Mutex::new(HashSet::new())),
+            triggers: Arc::new(Mutex::new(TriggerBook::default())),
             sdk_config,
             shutdown: Arc::new((Notify::new(), Mutex::new(false))),
         };
@@ -81,9 +120,47 @@ impl AggregatorContext {
             .map_err(|e| Error::Context(e.to_string()))?;
         aggregator_context.bls_aggregation_service = Some(Arc::new(Mutex::new(bls_service)));
 
+        // Initialize the avs registry service, used to check signatures on ingest
+        let avs_registry_service = aggregator_context
+            .eigenlayer_client()
+            .await
+            .map_err(|e| Error::Context(e.to_string()))?
+            .avs_registry_service_chain_caller_in_memory()
+            .await
+            .map_err(|e| Error::Context(e.to_string()))?;
+        aggregator_context.avs_registry_service = Some(Arc::new(avs_registry_service));
+
+        // Dormant orders created before a restart are only known to the contract
+        aggregator_context.track_dormant_orders().await?;
+
         Ok(aggregator_context)
     }
 
+    /// Tracks every order the contract holds as dormant, including the ones `triggerOrders`
+    /// skipped because the price it recorded had not crossed them yet.
+    pub async fn track_dormant_orders(&self) -> Result<(), Error> {
+        let provider = get_provider(&self.http_rpc_url);
+        let orders = OrderBookTaskManager::new(self.task_manager_address, provider)
+            .getAllOrders()
+            .call()
+            .await
+            .map_err(|e| Error::Chain(e.to_string()))?
+            ._0;
+        let tracked = self.triggers.lock().await.insert_all(&orders);
+        info!("Tracking {} dormant order(s)", tracked);
+        Ok(())
+    }
+
+    /// Leaves a task out of the aggregation, dropping the responses cached for it and
+    /// rejecting the ones still to come.
+    pub async fn skip_task(&self, task_index: TaskIndex) {
+        self.skipped_tasks.lock().await.insert(task_index);
+        self.response_cache
+            .lock()
+            .await
+            .retain(|resp| resp.task_response.referenceTaskIndex != task_index);
+    }
+
     pub async fn start(self) -> JoinHandle<()> {
         let aggregator = Arc::new(Mutex::new(self));
 
@@ -95,8 +172,14 @@ impl AggregatorContext {
             let process_handle =
                 tokio::spawn(Self::process_cached_responses(Arc::clone(&aggregator)));
 
-            // Wait for both tasks to complete
-            let (server_result, process_result) = tokio::join!(server_handle, process_handle);
+            let expiry_handle = tokio::spawn(Self::sweep_expired_orders(Arc::clone(&aggregator)));
+
+            let submit_handle =
+                tokio::spawn(Self::submit_aggregated_responses(Arc::clone(&aggregator)));
+
+            // Wait for all tasks to complete
+            let (server_result, process_result, expiry_result, submit_result) =
+                tokio::join!(server_handle, process_handle, expiry_handle, submit_handle);
 
             if let Err(e) = server_result {
                 error!("Server task failed: {}", e);
@@ -104,6 +187,12 @@ impl AggregatorContext {
             if let Err(e) = process_result {
                 error!("Process cached responses task failed: {}", e);
             }
+            if let Err(e) = expiry_result {
+                error!("Expired order sweep task failed: {}", e);
+            }
+            if let Err(e) = submit_result {
+                error!("Aggregated response submission task failed: {}", e);
+            }
 
             info!("Aggregator shutdown complete");
         })
@@ -142,13 +231,13 @@ impl AggregatorContext {
                             ))
                         })?;
 
-                    aggregator
-                        .lock()
-                        .await
+                    // Verifying reads the chain, the aggregator is not held meanwhile
+                    let mut context = aggregator.lock().await.clone();
+                    context
                         .process_signed_task_response(signed_task_response)
                         .await
                         .map(|_| Value::Bool(true))
-                        .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))
+                        .map_err(to_rpc_error)
                 }
             }
         });
@@ -227,13 +316,68 @@ impl AggregatorContext {
         let task_index = resp.task_response.referenceTaskIndex;
         let task_response_digest = keccak256(TaskResponse::abi_encode(&resp.task_response));
 
+        if self.tasks.lock().await.contains_key(&task_index) {
+            return self.process_response(resp).await;
+        }
+
+        // Responses to tasks not initialized yet are verified against the registry before
+        // they are cached, and again against the task's operators once they leave the cache
+        self.verify_registered(&resp, task_response_digest).await?;
+
         info!(
             "Caching signed task response for task index: {}, task response digest: {}",
             task_index, task_response_digest
         );
 
-        self.response_cache.lock().await.push_back(resp);
+        self.cache_response(resp).await
+    }
 
+    /// Checks that the response is signed by an operator registered in the quorums tasks
+    /// are created with, as read at the latest block.
+    async fn verify_registered(
+        &self,
+        resp: &SignedTaskResponse,
+        task_response_digest: TaskResponseDigest,
+    ) -> Result<(), Error> {
+        let block = get_provider(&self.http_rpc_url)
+            .get_block_number()
+            .await
+            .map_err(|e| Error::Chain(e.to_string()))?;
+        let block = u32::try_from(block).map_err(|e| Error::Conversion(e.to_string()))?;
+        let operators = self
+            .avs_registry_service
+            .as_ref()
+            .ok_or_else(|| Error::Context("AVS Registry Service not initialized".to_string()))?
+            .get_operators_avs_state_at_block(block, TASK_QUORUM_NUMBERS.as_ref())
+            .await
+            .map_err(|e| Error::Chain(e.to_string()))?;
+
+        let pub_key = operators
+            .get(&resp.operator_id)
+            .and_then(|operator| operator.operator_info.pub_keys.as_ref())
+            .ok_or(SignatureError::UnknownOperator(resp.operator_id))?;
+        if !verify_message(
+            pub_key.g2_pub_key.g2(),
+            &task_response_digest.0,
+            resp.signature.g1_point().g1(),
+        ) {
+            return Err(SignatureError::InvalidSignature(resp.operator_id).into());
+        }
+        Ok(())
+    }
+
+    /// Caches a response until its task is initialized, unless the task was skipped or
+    /// the cache is full.
+    async fn cache_response(&self, resp: SignedTaskResponse) -> Result<(), Error> {
+        let task_index = resp.task_response.referenceTaskIndex;
+        if self.skipped_tasks.lock().await.contains(&task_index) {
+            return Err(CacheError::SkippedTask(task_index).into());
+        }
+        let mut cache = self.response_cache.lock().await;
+        if cache.len() >= MAX_CACHED_RESPONSES {
+            return Err(CacheError::Full(cache.len()).into());
+        }
+        cache.push_back(resp);
         Ok(())
     }
 
@@ -255,30 +399,23 @@ impl AggregatorContext {
                         break;
                     }
 
-                    // Get responses to process while holding the lock briefly
+                    // Take the responses to process while holding the lock briefly
                     let responses_to_process = {
                         let guard = aggregator.lock().await;
-                        let cache = guard.response_cache.lock().await;
-                        cache.clone()
+                        let mut cache = guard.response_cache.lock().await;
+                        std::mem::take(&mut *cache)
                     };
 
                     // Process each response without holding the main lock
                     for resp in responses_to_process {
                         let res = {
                             let mut guard = aggregator.lock().await;
-                            guard.process_response(resp.clone()).await
+                            guard.process_response(resp).await
                         };
-                        match res {
-                            Ok(_) => {
-                                // Only remove from cache if processing succeeded
-                                let guard = aggregator.lock().await;
-                                let mut cache = guard.response_cache.lock().await;
-                                cache.pop_front();
-                            }
-                            Err(e) => {
-                                error!("Failed to process cached response: {:?}", e);
-                                // Continue processing other responses without failing
-                            }
+                        // Responses to unknown tasks are cached again by `process_response`,
+                        // rejected responses and those to skipped tasks are dropped
+                        if let Err(e) = res {
+                            error!("Failed to process cached response: {:?}", e);
                         }
                     }
                 }
@@ -292,6 +429,78 @@ impl AggregatorContext {
         }
     }
 
+    async fn sweep_expired_orders(aggregator: Arc<Mutex<Self>>) {
+        let mut interval = interval(EXPIRY_SWEEP_INTERVAL);
+
+        // Get shutdown components
+        let shutdown = {
+            let agg = aggregator.lock().await;
+            agg.shutdown.clone()
+        };
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    if *shutdown.1.lock().await {
+                        info!("Expired order sweep received shutdown signal");
+                        break;
+                    }
+
+                    let res = {
+                        let guard = aggregator.lock().await;
+                        guard.expire_orders().await
+                    };
+                    if let Err(e) = res {
+                        error!("Failed to expire orders: {:?}", e);
+                    }
+                }
+                _ = shutdown.0.notified() => {
+                    if *shutdown.1.lock().await {
+                        info!("Expired order sweep received shutdown signal");
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Marks every resting order whose expiry has passed as expired on chain.
+    async fn expire_orders(&self) -> Result<(), Error> {
+        let provider = get_provider(&self.http_rpc_url);
+        let task_manager = OrderBookTaskManager::new(self.task_manager_address, provider);
+
+        let orders = task_manager
+            .getAllOrders()
+            .call()
+            .await
+            .map_err(|e| Error::Chain(e.to_string()))?
+            ._0;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| Error::Runtime(e.to_string()))?
+            .as_secs();
+
+        let expired = expired_orders(&orders, U256::from(now));
+        if expired.is_empty() {
+            return Ok(());
+        }
+
+        info!("Expiring orders: {:?}", expired);
+        task_manager
+            .expireOrders(expired.into_iter().map(U256::from).collect())
+            .from(NetworkWallet::<Ethereum>::default_signer_address(
+                &self.wallet,
+            ))
+            .send()
+            .await
+            .map_err(|e| Error::Chain(e.to_string()))?
+            .get_receipt()
+            .await
+            .map_err(|e| Error::Chain(e.to_string()))?;
+
+        Ok(())
+    }
+
     async fn process_response(&mut self, resp: SignedTaskResponse) -> Result<(), Error> {
         let SignedTaskResponse {
             task_response,
@@ -307,160 +516,199 @@ impl AggregatorContext {
                 "Task {} not yet initialized, caching response for later processing",
                 task_index
             );
-            self.response_cache.lock().await.push_back(resp);
-            return Ok(());
+            return self.cache_response(resp).await;
         }
 
-        if self
-            .tasks_responses
+        info!(
+            "Processing signed task response for task index: {}, task response digest: {}",
+            task_index, task_response_digest
+        );
+
+        // Each operator is counted once, signatures past the quorum are not aggregated
+        {
+            let mut signatures = self.signatures.lock().await;
+            let task_signatures = signatures.get_mut(&task_index).ok_or_else(|| {
+                Error::Context(format!("No operator stakes known for task {}", task_index))
+            })?;
+            Self::verify_signature(task_signatures, &resp, task_response_digest)?;
+            task_signatures.check(operator_id)?;
+            if task_signatures.reached().is_some() {
+                task_signatures.record(task_response_digest, operator_id)?;
+                info!(
+                    "Quorum already reached for task index: {}, ignoring signature of operator {}",
+                    task_index, operator_id
+                );
+                return Ok(());
+            }
+        }
+
+        // Stored first so the response is known once its aggregate comes back
+        self.tasks_responses
             .lock()
             .await
             .entry(task_index)
             .or_default()
-            .contains_key(&task_response_digest)
-        {
-            info!(
-                "Task response digest already processed for task index: {}",
-                task_index
-            );
-            return Ok(());
-        }
+            .entry(task_response_digest)
+            .or_insert(task_response);
 
-        info!(
-            "Processing signed task response for task index: {}, task response digest: {}",
-            task_index, task_response_digest
-        );
+        // Every operator's signature is aggregated, the aggregated response is submitted
+        // by `submit_aggregated_responses` once the task reaches its quorum
+        self.bls_service()?
+            .lock()
+            .await
+            .process_new_signature(task_index, task_response_digest, signature, operator_id)
+            .await
+            .map_err(|e| Error::Context(e.to_string()))?;
 
-        // self.bls_aggregation_service
-        //     .as_ref()
-        //     .ok_or_else(|| {
-        //         std::io::Error::new(
-        //             std::io::ErrorKind::Other,
-        //             "BLS Aggregation Service not initialized",
-        //         )
-        //     })
-        //     .map_err(|e| Error::Context(e.to_string()))?
-        //     .lock()
-        //     .await
-        //     .process_new_signature(task_index, task_response_digest, signature, operator_id)
-        //     .await
-        //     .map_err(|e| Error::Context(e.to_string()))?;
-
-        if let Some(tasks_responses) = self.tasks_responses.lock().await.get_mut(&task_index) {
-            tasks_responses.insert(task_response_digest, task_response.clone());
+        // Only counted once aggregated, a rejected signature can be sent again
+        let progress = self
+            .signatures
+            .lock()
+            .await
+            .get_mut(&task_index)
+            .ok_or_else(|| {
+                Error::Context(format!("No operator stakes known for task {}", task_index))
+            })?
+            .record(task_response_digest, operator_id)?;
+        if progress == Progress::Reached {
+            info!("Quorum reached for task index: {}", task_index);
         }
 
         info!(
             "Successfully processed new signature for task index: {}",
             task_index
         );
+        Ok(())
+    }
 
-        // self.send_aggregated_response_to_contract(
-        //     BlsAggregationServiceResponse {
-        //         task_index,
-        //         task_response_digest,
-        //         non_signers_pub_keys_g1: vec![],
-        //         quorum_apks_g1: vec![],
-        //         signers_apk_g2: 
-        //         signers_agg_sig_g1: self.bls_aggregation_service.as_ref().unwrap().lock().await.process_new_signature(task_index, task_response_digest, signature, operator_id).unwrap().signers_agg_sig_g1,
-        //         non_signer_quorum_bitmap_indices: vec![],
-        //         quorum_apk_indices: vec![],
-        //         total_stake_indices: vec![],
-        //         non_signer_stake_indices: vec![],
-        //     },
-        // ).await?;
-
-        // let aggregated_response = self.bls_aggregation_service.as_ref().unwrap().lock().await.aggregated_response_receiver.lock().await.recv().await.unwrap().unwrap();
-
-        info!("Received aggregated response from BLS Aggregation Service");
-        self.send_aggregated_response_to_contract(task_index, task_response_digest).await?;
-
-        info!("Sent aggregated response to contract");
-
-        // if let Some(aggregated_response) = self
-        //     .bls_aggregation_service
-        //     .as_ref()
-        //     .ok_or_else(|| {
-        //         std::io::Error::new(
-        //             std::io::ErrorKind::Other,
-        //             "BLS Aggregation Service not initialized",
-        //         )
-        //     })
-        //     .map_err(|e| Error::Context(e.to_string()))?
-        //     .lock()
-        //     .await
-        //     .aggregated_response_receiver
-        //     .lock()
-        //     .await
-        //     .recv()
-        //     .await
-        // {
-        //     info!("Received aggregated response from BLS Aggregation Service");
-        //     let response = aggregated_response.map_err(|e| Error::Context(e.to_string()))?;
-        //     self.send_aggregated_response_to_contract(response).await?;
-        // } else {
-        //     info!("No aggregated response received from BLS Aggregation Service");
-        // }
+    /// Checks that the response is signed with the G2 public key the operator registered,
+    /// as read for the task at `taskCreatedBlock`.
+    fn verify_signature(
+        signatures: &TaskSignatures,
+        resp: &SignedTaskResponse,
+        task_response_digest: TaskResponseDigest,
+    ) -> Result<(), Error> {
+        let pub_key = signatures
+            .public_key(&resp.operator_id)
+            .ok_or(SignatureError::UnknownOperator(resp.operator_id))?;
+        if !verify_message(
+            pub_key.g2(),
+            &task_response_digest.0,
+            resp.signature.g1_point().g1(),
+        ) {
+            return Err(SignatureError::InvalidSignature(resp.operator_id).into());
+        }
         Ok(())
     }
 
+    fn bls_service(&self) -> Result<&Arc<Mutex<BlsAggServiceInMemory>>, Error> {
+        self.bls_aggregation_service
+            .as_ref()
+            .ok_or_else(|| Error::Context("BLS Aggregation Service not initialized".to_string()))
+    }
+
+    /// Submits every response aggregated by the BLS Aggregation Service to the contract.
+    async fn submit_aggregated_responses(aggregator: Arc<Mutex<Self>>) {
+        // Get shutdown components and the receiver of aggregated responses
+        let (shutdown, receiver) = {
+            let agg = aggregator.lock().await;
+            let receiver = match agg.bls_service() {
+                Ok(service) => service.lock().await.aggregated_response_receiver.clone(),
+                Err(e) => {
+                    error!("Cannot submit aggregated responses: {}", e);
+                    return;
+                }
+            };
+            (agg.shutdown.clone(), receiver)
+        };
+
+        loop {
+            tokio::select! {
+                aggregated_response = async { receiver.lock().await.recv().await } => {
+                    let Some(aggregated_response) = aggregated_response else {
+                        info!("BLS Aggregation Service stopped sending aggregated responses");
+                        break;
+                    };
+                    let response = match aggregated_response {
+                        Ok(response) => response,
+                        Err(e) => {
+                            error!("Failed to aggregate task response: {:?}", e);
+                            continue;
+                        }
+                    };
+                    info!(
+                        "Received aggregated response from BLS Aggregation Service for task index: {}",
+                        response.task_index
+                    );
+
+                    // Submitting waits for the receipt, the aggregator is not held meanwhile
+                    let context = aggregator.lock().await.clone();
+                    if let Err(e) = context.send_aggregated_response_to_contract(response).await {
+                        error!("Failed to send aggregated response to contract: {:?}", e);
+                    }
+                }
+                _ = shutdown.0.notified() => {
+                    if *shutdown.1.lock().await {
+                        info!("Aggregated response submission received shutdown signal");
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
     async fn send_aggregated_response_to_contract(
         &self,
-        task_index: TaskIndex,
-        task_response_digest: TaskResponseDigest,
-        // response: BlsAggregationServiceResponse,
+        response: BlsAggregationServiceResponse,
     ) -> Result<(), Error> {
-        // let non_signer_stakes_and_signature = NonSignerStakesAndSignature {
-        //     nonSignerPubkeys: response
-        //         .non_signers_pub_keys_g1
-        //         .into_iter()
-        //         .map(to_g1_point)
-        //         .collect(),
-        //     nonSignerQuorumBitmapIndices: response.non_signer_quorum_bitmap_indices,
-        //     quorumApks: response
-        //         .quorum_apks_g1
-        //         .into_iter()
-        //         .map(to_g1_point)
-        //         .collect(),
-        //     apkG2: to_g2_point(response.signers_apk_g2),
-        //     sigma: to_g1_point(response.signers_agg_sig_g1.g1_point()),
-        //     quorumApkIndices: response.quorum_apk_indices,
-        //     totalStakeIndices: response.total_stake_indices,
-        //     nonSignerStakeIndices: response.non_signer_stake_indices,
-        // };
-
-        // fn to_g1_point(pk: BlsG1Point) -> G1Point {
-        //     let pt = convert_to_g1_point(pk.g1()).expect("Invalid G1 point");
-        //     G1Point { X: pt.X, Y: pt.Y }
-        // }
-
-        // fn to_g2_point(pk: BlsG2Point) -> G2Point {
-        //     let pt = convert_to_g2_point(pk.g2()).expect("Invalid G2 point");
-        //     G2Point { X: pt.X, Y: pt.Y }
-        // }
+        let task_index = response.task_index;
+        let non_signer_stakes_and_signature = NonSignerStakesAndSignature {
+            nonSignerPubkeys: response
+                .non_signers_pub_keys_g1
+                .into_iter()
+                .map(to_g1_point)
+                .collect::<Result<_, _>>()?,
+            nonSignerQuorumBitmapIndices: response.non_signer_quorum_bitmap_indices,
+            quorumApks: response
+                .quorum_apks_g1
+                .into_iter()
+                .map(to_g1_point)
+                .collect::<Result<_, _>>()?,
+            apkG2: to_g2_point(response.signers_apk_g2)?,
+            sigma: to_g1_point(response.signers_agg_sig_g1.g1_point())?,
+            quorumApkIndices: response.quorum_apk_indices,
+            totalStakeIndices: response.total_stake_indices,
+            nonSignerStakeIndices: response.non_signer_stake_indices,
+        };
 
         let tasks = self.tasks.lock().await;
         let task_responses = self.tasks_responses.lock().await;
-        let task = tasks.get(&task_index).expect("Task not found");
+        let task = tasks
+            .get(&task_index)
+            .ok_or_else(|| Error::Context(format!("Task {} not found", task_index)))?;
         let task_response = task_responses
             .get(&task_index)
-            .and_then(|responses| responses.get(&task_response_digest))
-            .expect("Task response not found");
+            .and_then(|responses| responses.get(&response.task_response_digest))
+            .ok_or_else(|| Error::Context(format!("Response to task {} not found", task_index)))?;
+
+        if task_response.outcome == u8::from(MatchOutcome::Matched) {
+            // All fills of the task are submitted in a single transaction
+            info!(
+                "Submitting {} fill(s) and {} ring fill(s) for task index: {}",
+                task_response.fills.len(),
+                task_response.ringUpdates.len(),
+                task_index
+            );
+        } else {
+            // Nothing crossed, the response only acknowledges the incoming order
+            info!(
+                "Submitting acknowledgement for unmatched task index: {}, outcome: {}",
+                task_index, task_response.outcome
+            );
+        }
 
         let provider = get_provider(&self.http_rpc_url);
-        let task_manager =
-            OrderBookTaskManager::new(self.task_manager_address, provider.clone());
-
-        let non_signer_stakes_and_signature: NonSignerStakesAndSignature = NonSignerStakesAndSignature {
-            nonSignerPubkeys: vec![],
-            nonSignerQuorumBitmapIndices: vec![],
-            quorumApks: vec![],
-            apkG2: G2Point { X: [alloy_primitives::Uint::from(0u64); 2], Y: [alloy_primitives::Uint::from(0u64); 2] },
-            sigma: G1Point { X: alloy_primitives::Uint::from(0u64), Y: alloy_primitives::Uint::from(0u64) },
-            quorumApkIndices: vec![],
-            totalStakeIndices: vec![],
-            nonSignerStakeIndices: vec![],
-        };
+        let task_manager = OrderBookTaskManager::new(self.task_manager_address, provider.clone());
 
         let _ = task_manager
             .respondToTask(
@@ -479,13 +727,42 @@ impl AggregatorContext {
             .map_err(|e| Error::Chain(e.to_string()))?;
 
         info!(
-            "Sent aggregated response to contract for task index"            
+            "Sent aggregated response to contract for task index: {}",
+            task_index
         );
 
         Ok(())
     }
 }
 
+/// Maps rejected signatures and uncached responses to their own JSON-RPC error codes, so
+/// operators can tell a bad signature from a missing registration or a full cache.
+fn to_rpc_error(error: Error) -> jsonrpc_core::Error {
+    let code = match &error {
+        Error::Signature(SignatureError::InvalidSignature(_)) => INVALID_SIGNATURE_ERROR,
+        Error::Signature(SignatureError::UnknownOperator(_)) => UNKNOWN_OPERATOR_ERROR,
+        Error::Signature(SignatureError::DuplicateOperator(_)) => DUPLICATE_OPERATOR_ERROR,
+        Error::Cache(CacheError::Full(_)) => CACHE_FULL_ERROR,
+        Error::Cache(CacheError::SkippedTask(_)) => SKIPPED_TASK_ERROR,
+        _ => return jsonrpc_core::Error::invalid_params(error.to_string()),
+    };
+    jsonrpc_core::Error {
+        code: ErrorCode::ServerError(code),
+        message: error.to_string(),
+        data: None,
+    }
+}
+
+fn to_g1_point(pk: BlsG1Point) -> Result<G1Point, Error> {
+    let pt = convert_to_g1_point(pk.g1()).map_err(|e| Error::Conversion(e.to_string()))?;
+    Ok(G1Point { X: pt.X, Y: pt.Y })
+}
+
+fn to_g2_point(pk: BlsG2Point) -> Result<G2Point, Error> {
+    let pt = convert_to_g2_point(pk.g2()).map_err(|e| Error::Conversion(e.to_string()))?;
+    Ok(G2Point { X: pt.X, Y: pt.Y })
+}
+
 #[async_trait::async_trait]
 impl BackgroundService for AggregatorContext {
     async fn start(&self) -> Result<oneshot::Receiver<Result<(), RunnerError>>, RunnerError> {
@@ -511,4 +788,4 @@ impl BackgroundService for AggregatorContext {
 
         Ok(result_rx)
     }
-}
\ No newline at end of file
+}
@@ -1,10 +1,23 @@
+use crate::accounting::FeeLedger;
 use crate::contexts::client::AggregatorClient;
+use crate::matching::auction::BatchQueue;
+use crate::matching::engine::MatchingConfig;
+use crate::tokens::TokenRegistry;
 use blueprint_sdk::config::GadgetConfiguration;
 use blueprint_sdk::macros::contexts::KeystoreContext;
+use std::sync::Arc;
+use tokio::sync::Mutex;
 
 #[derive(Clone, KeystoreContext)]
 pub struct EigenOrderContext {
     pub client: AggregatorClient,
+    pub matching: MatchingConfig,
+    /// Fees charged in the tasks this operator signed.
+    pub fees: Arc<Mutex<FeeLedger>>,
+    /// Decimals and symbols of the traded tokens, used to display prices.
+    pub tokens: Arc<Mutex<TokenRegistry>>,
+    /// Tasks of batch auction markets waiting for their window to close.
+    pub batches: Arc<Mutex<BatchQueue>>,
     #[config]
     pub std_config: GadgetConfiguration,
-}
\ No newline at end of file
+}
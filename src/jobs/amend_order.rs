@@ -0,0 +1,92 @@
+use crate::contexts::order::EigenOrderContext;
+use crate::jobs::create_order::orders_at;
+use crate::matching::amend::amend;
+use crate::OrderBookTaskManager::OrderAmended;
+use crate::{Error, OrderBookTaskManager, ProcessorError, ORDER_BOOK_TASK_MANAGER_ABI_STRING};
+use alloy_primitives::Address;
+use blueprint_sdk::event_listeners::evm::EvmContractEventListener;
+use blueprint_sdk::logging::{error, info, warn};
+use blueprint_sdk::macros::job;
+use std::convert::Infallible;
+
+/// Logs an amended order and whether it kept its time priority.
+///
+/// This job is triggered by the `OrderAmended` event emitted by the `OrderBookTaskManager`
+/// when a user re-quotes one of their orders. The contract applies the amendment and tasks
+/// are matched against the orders it holds at their block, so nothing has to be replayed
+/// on the operator's side. The order is read as it was before the amendment's block to
+/// report whether the amendment kept its time priority, size-down amendments do, see
+/// [`crate::matching::amend::amend`].
+/// The job returns 1 if the amendment was checked against the previous order.
+/// The job returns 0 if the previous order could not be read or cannot be amended.
+#[job(
+    id = 3,
+    params(event, task_manager, block),
+    event_listener(
+        listener = EvmContractEventListener<EigenOrderContext, OrderBookTaskManager::OrderAmended>,
+        instance = OrderBookTaskManager,
+        abi = ORDER_BOOK_TASK_MANAGER_ABI_STRING,
+        pre_processor = convert_event_to_inputs,
+    ),
+)]
+pub async fn amend_order(
+    ctx: EigenOrderContext,
+    event: OrderAmended,
+    task_manager: Address,
+    block: u64,
+) -> Result<u32, Infallible> {
+    let Ok(index) = usize::try_from(event.orderIndex) else {
+        warn!("Ignoring amendment of unknown order {}", event.orderIndex);
+        return Ok(0);
+    };
+
+    let orders = match orders_at(
+        &ctx.std_config.http_rpc_endpoint,
+        task_manager,
+        block.saturating_sub(1),
+    )
+    .await
+    {
+        Ok(orders) => orders,
+        Err(e) => {
+            error!("Failed to read order {} from the contract: {}", index, e);
+            return Ok(0);
+        }
+    };
+    let Some(previous) = orders.get(index) else {
+        warn!("Order {} did not exist before its amendment", index);
+        return Ok(0);
+    };
+
+    match amend(
+        previous,
+        event.amount_owned,
+        event.amount_not_owned,
+        event.timestamp,
+    ) {
+        Ok((_, priority)) => info!(
+            "Order {} was amended to give {} for {}, time priority {:?}",
+            index, event.amount_owned, event.amount_not_owned, priority
+        ),
+        Err(e) => {
+            warn!("Order {} was amended on chain but {}", index, e);
+            return Ok(0);
+        }
+    }
+
+    Ok(1)
+}
+
+/// Converts the event to inputs.
+///
+/// Uses a tuple to represent the return type because
+/// the macro will index all values in the #[job] function
+/// and parse the return type by the index.
+pub async fn convert_event_to_inputs(
+    (event, log): (OrderAmended, alloy_rpc_types::Log),
+) -> Result<Option<(OrderAmended, Address, u64)>, ProcessorError> {
+    let block = log
+        .block_number
+        .ok_or_else(|| Error::Conversion("OrderAmended log has no block number".to_string()))?;
+    Ok(Some((event, log.address(), block)))
+}
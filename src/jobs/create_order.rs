@@ -1,33 +1,53 @@
 #![allow(dead_code)]
+use crate::accounting::epoch_of;
+use crate::constants::FEE_EPOCH_BLOCKS;
 use crate::contexts::client::SignedTaskResponse;
 use crate::contexts::order::EigenOrderContext;
-use crate::IOrderBookTaskManager::{TaskResponse, Order};
+use crate::jobs::initialize_task::BLOCK_TIME_SECONDS;
+use crate::matching::auction::{self, first_block, settlement_block, window_of, WindowTasks};
+use crate::matching::book::TaskBook;
+use crate::matching::engine::{match_order, MatchOutcome};
+use crate::matching::market::Market;
+use crate::matching::price::Price;
+use crate::matching::spec::Rejection;
+use crate::matching::stp::SelfTradePrevention;
+use crate::IOrderBookTaskManager::{Fill, Order, TaskResponse};
 use crate::{
-    OrderBookTaskManager, ProcessorError, ORDER_BOOK_TASK_MANAGER_ABI_STRING,
+    Error, OrderBookTaskManager, ProcessorError, ORDER_BOOK_TASK_MANAGER_ABI_STRING,
 };
-use alloy_primitives::{keccak256, Bytes, U256};
+use alloy_primitives::{keccak256, Address, Bytes, U256};
+use alloy_provider::Provider;
+use alloy_rpc_types::BlockId;
 use alloy_sol_types::SolType;
 use blueprint_sdk::contexts::keystore::KeystoreContext;
 use blueprint_sdk::crypto::bn254::ArkBlsBn254;
 use blueprint_sdk::event_listeners::evm::EvmContractEventListener;
 use blueprint_sdk::keystore::backends::Backend;
-use blueprint_sdk::logging::{error, info};
+use blueprint_sdk::logging::{error, info, warn};
 use blueprint_sdk::macros::ext::keystore::backends::bn254::Bn254Backend;
 use blueprint_sdk::macros::job;
 use color_eyre::Result;
+use blueprint_sdk::eigensdk::common::get_provider;
 use blueprint_sdk::eigensdk::crypto_bls::BlsKeyPair;
 use blueprint_sdk::eigensdk::crypto_bls::OperatorId;
 use std::convert::Infallible;
+use std::time::Duration;
 
 /// Sends a signed task response to the BLS Aggregator.
 ///
 /// This job is triggered by the `NewTaskCreated` event emitted by the `OrderBookTaskManager`.
-/// The job creates a limit order and sends the signed task response to the BLS Aggregator.
-/// The job returns 1 if the task response was sent successfully.
-/// The job returns 0 if the task response failed to send or failed to get the BLS key.
+/// The job reads the orders held by the contract at the task's block, matches the new order
+/// against them, filling it against as many resting orders as needed, and sends the signed
+/// task response to the BLS Aggregator.
+/// Orders of batch auction markets are instead queued until their block window
+/// closes, and the whole window is settled in the response to its last task, which
+/// answers every other task of the window.
+/// The job returns 1 if the task response was sent successfully or the order was queued.
+/// The job returns 0 if the orders could not be read, the task response failed to send or
+/// failed to get the BLS key.
 #[job(
     id = 0,
-    params(order, orderbook, task_created_block, quorum_numbers, quorum_threshold_percentage, task_index),
+    params(task_created_block, quorum_numbers, quorum_threshold_percentage, task_index, task_manager),
     event_listener(
         listener = EvmContractEventListener<EigenOrderContext, OrderBookTaskManager::NewTaskCreated>,
         instance = OrderBookTaskManager,
@@ -37,99 +57,209 @@ use std::convert::Infallible;
 )]
 pub async fn order_eigen(
     ctx: EigenOrderContext,
-    order: Order,
-    orderbook: Vec<Order>,
     task_created_block: u32,
     quorum_numbers: Bytes,
     quorum_threshold_percentage: u8,
     task_index: u32,
+    task_manager: Address,
 ) -> std::result::Result<u32, Infallible> {
-    let client = ctx.client.clone();
-
-    info!("Finding matches for task index: {}", task_index);
-
-    let mut new_order = order.clone();
-    let mut new_other_order = order.clone();
-    let mut matched_order_index = U256::from(0);
-
-    for (index, other_order) in orderbook.iter().enumerate() {
-        if order.user == other_order.user {
-            continue;
-        }
-
-        if order.isFilled || other_order.isFilled {
-            continue;
-        }
-
-        if other_order.token_owned == order.token_owned {
-            continue;
-        } 
-        
-        let price_for_user = order.amount_owned / order.amount_not_owned;
-        let price_for_other_user = other_order.amount_owned / other_order.amount_not_owned;   
+    info!(
+        "Finding matches for task index: {} using {} strategy",
+        task_index,
+        ctx.matching.strategy.name()
+    );
 
-        if (price_for_other_user < price_for_user) {
-            continue;
-        }
-        
-        let diff = if price_for_user >= price_for_other_user {
-            price_for_user - price_for_other_user
-        } else {
-            price_for_other_user - price_for_user
+    // The snapshot in the event misses cancellations and amendments made since the task was
+    // created, every operator reads the same block instead
+    let TaskBook { order, orderbook } =
+        match task_book(&ctx, task_manager, task_created_block, task_index).await {
+            Ok(book) => book,
+            Err(e) => {
+                error!(
+                    "Failed to read the orderbook for task index {}: {}",
+                    task_index, e
+                );
+                return Ok(0);
+            }
         };
 
-        let percentage_difference = (diff / ((price_for_user + price_for_other_user) / alloy_primitives::Uint::from(2))) * alloy_primitives::Uint::from(100);
-        if percentage_difference > order.slippage {
-            continue;
+    // Prices are matched in base units, token metadata is only needed to display them
+    let mut tokens = ctx.tokens.lock().await;
+    for token in [order.token_owned, order.token_not_owned] {
+        if let Err(e) = tokens.resolve(&ctx.std_config.http_rpc_endpoint, token).await {
+            warn!("Failed to resolve metadata of token {}: {}", token, e);
         }
+    }
+    if let Some(limit) = Price::of(&order) {
+        info!(
+            "Limit price for task index {}: {}",
+            task_index,
+            tokens.format_price(limit, &order.token_owned, &order.token_not_owned)
+        );
+    }
+    drop(tokens);
 
-        matched_order_index = U256::from(index);
-
-        if other_order.amount_not_owned == order.amount_not_owned {            
-            new_order.isFilled = true;       
-            new_order.amount_not_owned = U256::from(0);                 
-            new_other_order.isFilled = true;
-            new_other_order.amount_not_owned = U256::from(0);
+    // Orders rejected by the market specification are answered right away
+    let batch_market = Market::of(&order).filter(|market| {
+        ctx.matching.batch_markets.contains(market) && ctx.matching.markets.validate(&order).is_ok()
+    });
+    if let Some(market) = batch_market {
+        let window = window_of(task_created_block, ctx.matching.batch_window_blocks);
+        let opened = match ctx.batches.lock().await.add(market, window) {
+            Ok(opened) => opened,
+            Err(e) => {
+                error!("Failed to queue task index {}: {}", task_index, e);
+                return Ok(0);
+            }
+        };
+        info!(
+            "Queued task index {} for the batch auction of {} in window {}",
+            task_index, market, window
+        );
+        if opened {
+            tokio::spawn(settle_batch(ctx.clone(), task_manager, market, window));
         }
+        return Ok(1);
+    }
 
-        if other_order.amount_not_owned > order.amount_not_owned {
-            new_order.isFilled = true;
-            new_other_order.isPartiallyFilled = true;
-            new_order.amount_not_owned = U256::from(0);
-            new_other_order.amount_not_owned = other_order.amount_not_owned - order.amount_not_owned;
+    let result = match match_order(&order, &orderbook, &ctx.matching).map_err(Error::from) {
+        Ok(result) => result,
+        Err(e) => {
+            error!("Failed to match order for task index {}: {}", task_index, e);
+            return Ok(0);
         }
-
-        if other_order.amount_not_owned < order.amount_not_owned {
-            new_other_order.isFilled = true;
-            new_other_order.amount_not_owned = U256::from(0);
-            new_order.isPartiallyFilled = true;
-            new_order.amount_not_owned = order.amount_not_owned - other_order.amount_not_owned;
+    };
+    info!(
+        "Found {} fill(s) for task index: {}, outcome: {:?}",
+        result.fills.len(),
+        task_index,
+        result.outcome()
+    );
+    if result.is_match() {
+        let tokens = ctx.tokens.lock().await;
+        for fill in &result.fills {
+            info!(
+                "Filled {} against order {} at {}",
+                fill.amount,
+                fill.index,
+                tokens.format_price(fill.price, &order.token_owned, &order.token_not_owned)
+            );
         }
-
-        break;
+    }
+    for ring in &result.rings {
+        let hops: Vec<usize> = ring.hops.iter().map(|hop| hop.index).collect();
+        info!(
+            "Filled {} through ring {:?} for {} for task index: {}",
+            ring.received, hops, ring.given, task_index
+        );
+    }
+    if let Some(rejection) = result.rejection {
+        info!(
+            "Rejected order for task index {}: {:?}",
+            task_index, rejection
+        );
+    }
+    if !result.expired.is_empty() {
+        info!(
+            "Skipped expired orders {:?} for task index: {}",
+            result.expired, task_index
+        );
+    }
+    if let Some(mode) = result.self_trade {
+        info!(
+            "Applied self-trade prevention {:?} for task index: {}",
+            mode, task_index
+        );
+    }
+    if result.new_order.isCancelled {
+        info!("Order for task index {} will not rest in the book", task_index);
+    }
+    if !result.is_balanced(&order, &orderbook) {
+        error!(
+            "Fills for task index {} do not balance, refusing to sign the response",
+            task_index
+        );
+        return Ok(0);
     }
 
     // Create a TaskResponse object
     let task_response = TaskResponse {
-        referenceTaskIndex: task_index,        
-        newOrder: new_order,
-        newOtherOrder: new_other_order,      
-        matchedOrderIndex: matched_order_index,  
+        referenceTaskIndex: task_index,
+        outcome: result.outcome().into(),
+        newOrder: result.new_order.clone(),
+        fills: result
+            .fills
+            .iter()
+            .map(|fill| Fill {
+                orderIndex: U256::from(fill.index),
+                newOrder: fill.new_order.clone(),
+                makerFee: fill.maker_fee,
+            })
+            .collect(),
+        takerFee: result.taker_fee(),
+        rejectionReason: Rejection::code(result.rejection),
+        selfTradeOutcome: SelfTradePrevention::outcome(result.self_trade),
+        selfTradeUpdates: result
+            .self_trade_updates
+            .iter()
+            .map(|update| Fill {
+                orderIndex: U256::from(update.index),
+                newOrder: update.new_order.clone(),
+                makerFee: U256::ZERO,
+            })
+            .collect(),
+        ringUpdates: result
+            .rings
+            .iter()
+            .flat_map(|ring| &ring.hops)
+            .map(|hop| Fill {
+                orderIndex: U256::from(hop.index),
+                newOrder: hop.new_order.clone(),
+                makerFee: U256::ZERO,
+            })
+            .collect(),
+        isBatch: false,
+        clearingPriceNumerator: U256::ZERO,
+        clearingPriceDenominator: U256::ZERO,
+        batchTasks: vec![],
     };
 
+    if !sign_and_send(&ctx, task_response).await {
+        return Ok(0);
+    }
+
+    if result.is_match() {
+        let epoch = epoch_of(task_created_block, *FEE_EPOCH_BLOCKS);
+        ctx.fees.lock().await.record(&order, &result, epoch);
+        info!(
+            "Recorded fees for task index: {} in epoch {}, taker fee: {}",
+            task_index,
+            epoch,
+            result.taker_fee()
+        );
+    }
+
+    Ok(1)
+}
+
+/// Signs a task response with the operator's BLS key and sends it to the BLS Aggregator.
+///
+/// Returns false if the key is unavailable or the response failed to send.
+async fn sign_and_send(ctx: &EigenOrderContext, task_response: TaskResponse) -> bool {
     // info!("The task response is {:#?}", task_response);
 
+    let client = ctx.client.clone();
     let bn254_public = ctx.keystore().first_local::<ArkBlsBn254>().unwrap();
     let bn254_secret = match ctx.keystore().expose_bls_bn254_secret(&bn254_public) {
         Ok(s) => match s {
             Some(s) => s,
-            None => return Ok(0),
+            None => return false,
         },
-        Err(_) => return Ok(0),
+        Err(_) => return false,
     };
     let bls_key_pair = match BlsKeyPair::new(bn254_secret.0.to_string()) {
         Ok(pair) => pair,
-        Err(e) => return Ok(0),
+        Err(e) => return false,
     };
     let operator_id = operator_id_from_key(bls_key_pair.clone());
 
@@ -152,10 +282,296 @@ pub async fn order_eigen(
     );
     if let Err(e) = client.send_signed_task_response(signed_response).await {
         error!("Failed to send signed task response: {:?}", e);
-        return Ok(0);
+        return false;
     }
 
-    Ok(1)
+    true
+}
+
+/// Settles every batch auction persisted before a restart, once its window has closed.
+pub async fn resume_batches(ctx: &EigenOrderContext, task_manager: Address) {
+    let batches = ctx.batches.lock().await;
+    for batch in batches.pending() {
+        info!(
+            "Resuming the batch auction of {} in window {}",
+            batch.market, batch.window
+        );
+        tokio::spawn(settle_batch(
+            ctx.clone(),
+            task_manager,
+            batch.market,
+            batch.window,
+        ));
+    }
+}
+
+/// Settles the batch auction of `market` for `window` once the window has closed.
+///
+/// Waits for the first block after the window, then reads the window's tasks from the
+/// `NewTaskCreated` events of its blocks, see [`WindowTasks`]. It clears the orders held
+/// by the contract at the window's last block, up to the window's last task, and responds
+/// to that task with every order the auction changed. The response lists the window's
+/// other tasks, so the contract marks them as answered too. Every operator reads the
+/// same blocks, so they all sign the same response.
+async fn settle_batch(ctx: EigenOrderContext, task_manager: Address, market: Market, window: u64) {
+    let settlement = settlement_block(window, ctx.matching.batch_window_blocks);
+    let provider = get_provider(&ctx.std_config.http_rpc_endpoint);
+    loop {
+        match provider.get_block_number().await {
+            Ok(block) if block >= settlement => break,
+            Ok(_) => {}
+            Err(e) => warn!("Failed to get the block number: {}", e),
+        }
+        tokio::time::sleep(Duration::from_secs(BLOCK_TIME_SECONDS.into())).await;
+    }
+
+    let (tasks, orderbook) = match read_window(&ctx, task_manager, market, window).await {
+        Ok(read) => read,
+        Err(e) => {
+            error!(
+                "Failed to read window {} of the batch auction of {}: {}",
+                window, market, e
+            );
+            return;
+        }
+    };
+    let Some(task_index) = tasks.task_index() else {
+        warn!(
+            "No task of the batch auction of {} was created in window {}",
+            market, window
+        );
+        settled(&ctx, &market, window).await;
+        return;
+    };
+    match is_answered(&ctx, task_manager, task_index).await {
+        Ok(true) => {
+            info!(
+                "Window {} of the batch auction of {} was already settled",
+                window, market
+            );
+            settled(&ctx, &market, window).await;
+            return;
+        }
+        Ok(false) => {}
+        Err(e) => warn!(
+            "Failed to check whether task index {} was answered: {}",
+            task_index, e
+        ),
+    }
+    let order = &orderbook[task_index as usize];
+    let result = match auction::clear(market, &orderbook, order.timestamp) {
+        Ok(result) => result,
+        Err(e) => {
+            error!(
+                "Failed to clear the batch auction for task index {}: {}",
+                task_index, e
+            );
+            return;
+        }
+    };
+    if !result.is_balanced(&market, &orderbook) {
+        error!(
+            "Batch auction for task index {} does not balance, refusing to sign the response",
+            task_index
+        );
+        return;
+    }
+    match result.clearing_price {
+        Some(price) => info!(
+            "Cleared batch auction of {} in window {} at {}, volume {}, {} order(s) changed",
+            market,
+            window,
+            ctx.tokens
+                .lock()
+                .await
+                .format_price(price, &market.token1, &market.token0),
+            result.volume,
+            result.updates.len()
+        ),
+        None => info!(
+            "Nothing crossed in the batch auction of {} in window {}",
+            market, window
+        ),
+    }
+    if !result.expired.is_empty() {
+        info!(
+            "Skipped expired orders {:?} in the batch auction for task index: {}",
+            result.expired, task_index
+        );
+    }
+
+    let new_order = result
+        .updates
+        .iter()
+        .find(|update| update.index == task_index as usize)
+        .map_or_else(|| order.clone(), |update| update.new_order.clone());
+    let outcome = if !result.volume.is_zero() {
+        MatchOutcome::Matched
+    } else if new_order.isCancelled {
+        MatchOutcome::Cancelled
+    } else {
+        MatchOutcome::Resting
+    };
+    let (numerator, denominator) = result
+        .clearing_price
+        .map_or((U256::ZERO, U256::ZERO), |price| {
+            (price.numerator(), price.denominator())
+        });
+
+    let task_response = TaskResponse {
+        referenceTaskIndex: task_index,
+        outcome: outcome.into(),
+        rejectionReason: 0,
+        newOrder: new_order,
+        fills: result
+            .updates
+            .iter()
+            .filter(|update| update.index != task_index as usize)
+            .map(|update| Fill {
+                orderIndex: U256::from(update.index),
+                newOrder: update.new_order.clone(),
+                makerFee: U256::ZERO,
+            })
+            .collect(),
+        takerFee: U256::ZERO,
+        selfTradeOutcome: 0,
+        selfTradeUpdates: vec![],
+        ringUpdates: vec![],
+        isBatch: true,
+        clearingPriceNumerator: numerator,
+        clearingPriceDenominator: denominator,
+        batchTasks: tasks.earlier_tasks(),
+    };
+    if !sign_and_send(&ctx, task_response).await {
+        return;
+    }
+    settled(&ctx, &market, window).await;
+}
+
+/// Reads the tasks of `window` in the batch auction of `market` and the orders held by the
+/// contract at the block of the window's last task, up to and including that task.
+///
+/// The book is read at the last task's block rather than at the end of the window, so the
+/// contract drops the same changed orders from the response as the operators withheld.
+async fn read_window(
+    ctx: &EigenOrderContext,
+    task_manager: Address,
+    market: Market,
+    window: u64,
+) -> Result<(WindowTasks, Vec<Order>), Error> {
+    let first = first_block(window, ctx.matching.batch_window_blocks);
+    let last = settlement_block(window, ctx.matching.batch_window_blocks) - 1;
+    let created = OrderBookTaskManager::new(
+        task_manager,
+        get_provider(&ctx.std_config.http_rpc_endpoint),
+    )
+    .NewTaskCreated_filter()
+    .from_block(first)
+    .to_block(last)
+    .query()
+    .await
+    .map_err(|e| Error::Chain(e.to_string()))?;
+    let tasks = WindowTasks::read(
+        &market,
+        created
+            .iter()
+            .map(|(event, _)| (event.taskIndex, &event.task.order)),
+        &ctx.matching.markets,
+    );
+
+    let Some(task_index) = tasks.task_index() else {
+        return Ok((tasks, Vec::new()));
+    };
+    let task_block = created
+        .iter()
+        .find(|(event, _)| event.taskIndex == task_index)
+        .map(|(event, _)| event.task.taskCreatedBlock)
+        .expect("the window's tasks were read from these events");
+    let TaskBook {
+        order,
+        mut orderbook,
+    } = task_book(ctx, task_manager, task_block, task_index).await?;
+    orderbook.push(order);
+    Ok((tasks, orderbook))
+}
+
+/// Whether the contract already holds a response to the task at `task_index`.
+async fn is_answered(
+    ctx: &EigenOrderContext,
+    task_manager: Address,
+    task_index: u32,
+) -> Result<bool, Error> {
+    let response = OrderBookTaskManager::new(
+        task_manager,
+        get_provider(&ctx.std_config.http_rpc_endpoint),
+    )
+    .allTaskResponses(task_index)
+    .call()
+    .await
+    .map_err(|e| Error::Chain(e.to_string()))?
+    ._0;
+    Ok(!response.is_zero())
+}
+
+/// Drops the batch of `market` for `window` from the queue.
+async fn settled(ctx: &EigenOrderContext, market: &Market, window: u64) {
+    if let Err(e) = ctx.batches.lock().await.settled(market, window) {
+        error!(
+            "Failed to drop window {} of the batch auction of {}: {}",
+            window, market, e
+        );
+    }
+}
+
+/// Reads the orders held by the contract at `task_block`, the block of the task at
+/// `task_index`, and splits them for the task.
+async fn task_book(
+    ctx: &EigenOrderContext,
+    task_manager: Address,
+    task_block: u32,
+    task_index: u32,
+) -> Result<TaskBook, Error> {
+    let rpc_url = &ctx.std_config.http_rpc_endpoint;
+    let orders = orders_at(rpc_url, task_manager, task_block.into()).await?;
+    let changed_blocks = changed_blocks_at(rpc_url, task_manager, task_block.into()).await?;
+    Ok(TaskBook::split(
+        task_index,
+        orders,
+        &changed_blocks,
+        task_block,
+    )?)
+}
+
+/// Reads every order held by the contract at `block`.
+pub(crate) async fn orders_at(
+    http_rpc_url: &str,
+    task_manager: Address,
+    block: u64,
+) -> Result<Vec<Order>, Error> {
+    let provider = get_provider(http_rpc_url);
+    Ok(OrderBookTaskManager::new(task_manager, provider)
+        .getAllOrders()
+        .block(BlockId::number(block))
+        .call()
+        .await
+        .map_err(|e| Error::Chain(e.to_string()))?
+        ._0)
+}
+
+/// Reads the block at which each order held by the contract at `block` last changed.
+async fn changed_blocks_at(
+    http_rpc_url: &str,
+    task_manager: Address,
+    block: u64,
+) -> Result<Vec<u32>, Error> {
+    let provider = get_provider(http_rpc_url);
+    Ok(OrderBookTaskManager::new(task_manager, provider)
+        .getOrderChangedBlocks()
+        .block(BlockId::number(block))
+        .call()
+        .await
+        .map_err(|e| Error::Chain(e.to_string()))?
+        ._0)
 }
 
 /// Generate the Operator ID from the BLS Keypair
@@ -178,23 +594,20 @@ pub fn operator_id_from_key(key: BlsKeyPair) -> OperatorId {
 /// the macro will index all values in the #[job] function
 /// and parse the return type by the index.
 pub async fn convert_event_to_inputs(
-    (event, _log): (
+    (event, log): (
         OrderBookTaskManager::NewTaskCreated,
         alloy_rpc_types::Log,
     ),
-) -> Result<Option<(Order, Vec<Order>, u32, Bytes, u8, u32)>, ProcessorError> {
+) -> Result<Option<(u32, Bytes, u8, u32, Address)>, ProcessorError> {
     let task_index = event.taskIndex;
-    let order = event.task.order;
-    let orderbook = event.task.orderbook;
     let task_created_block = event.task.taskCreatedBlock;
     let quorum_numbers = event.task.quorumNumbers;
     let quorum_threshold_percentage = event.task.quorumThresholdPercentage.try_into().unwrap();
     Ok(Some((
-        order,
-        orderbook,
         task_created_block,
         quorum_numbers,
         quorum_threshold_percentage,
         task_index,
+        log.address(),
     )))
 }
\ No newline at end of file
@@ -1,15 +1,25 @@
+use crate::constants::BATCH_WINDOW_BLOCKS;
+use crate::quorum::TaskSignatures;
 use crate::IOrderBookTaskManager::Task;
 use crate::{
-    contexts::aggregator::AggregatorContext, OrderBookTaskManager, ProcessorError,
+    contexts::aggregator::AggregatorContext, Error, OrderBookTaskManager, ProcessorError,
     ORDER_BOOK_TASK_MANAGER_ABI_STRING,
 };
+use alloy_primitives::U256;
+use blueprint_sdk::contexts::eigenlayer::EigenlayerContext;
+use blueprint_sdk::eigensdk::crypto_bls::{BlsG2Point, OperatorId};
+use blueprint_sdk::eigensdk::services_avsregistry::AvsRegistryService;
 use blueprint_sdk::event_listeners::evm::EvmContractEventListener;
-use blueprint_sdk::logging::info;
+use blueprint_sdk::logging::{error, info, warn};
+use std::collections::HashMap;
 use std::convert::Infallible;
+use std::time::Duration;
 use alloy_rpc_types::Log;
 
 const TASK_CHALLENGE_WINDOW_BLOCK: u32 = 100;
-const BLOCK_TIME_SECONDS: u32 = 12;
+/// Reads of the operators of a task before it is given up on, one block apart.
+const OPERATOR_READ_ATTEMPTS: u32 = 5;
+pub(crate) const BLOCK_TIME_SECONDS: u32 = 12;
 
 /// Initializes the task for the aggregator server
 #[blueprint_sdk::job(
@@ -29,10 +39,49 @@ pub async fn initialize_bls_task(
 ) -> Result<u32, Infallible> {
     info!("Initializing task for BLS aggregation");
 
+    // Stakes and public keys are read at the block the task was created in, as the contract
+    // checks them. They are known before the task, so no signature to the task goes uncounted
+    let mut attempt = 1;
+    let (operators, public_keys) = loop {
+        let read = match operator_stakes(&ctx, &task).await {
+            Ok(operators) => operator_public_keys(&ctx, &task)
+                .await
+                .map(|public_keys| (operators, public_keys)),
+            Err(e) => Err(e),
+        };
+        match read {
+            Ok(read) => break read,
+            Err(e) if attempt < OPERATOR_READ_ATTEMPTS => {
+                warn!(
+                    "Failed to read the operators of task {} (attempt {}): {}",
+                    task_index, attempt, e
+                );
+                attempt += 1;
+                tokio::time::sleep(Duration::from_secs(BLOCK_TIME_SECONDS.into())).await;
+            }
+            Err(e) => {
+                // Without its operators no signature could be counted, the task is left out
+                error!(
+                    "Failed to read the operators of task {}, not initializing it: {}",
+                    task_index, e
+                );
+                ctx.skip_task(task_index).await;
+                return Ok(0);
+            }
+        }
+    };
+    ctx.signatures.lock().await.insert(
+        task_index,
+        TaskSignatures::new(task.quorumThresholdPercentage, operators)
+            .with_public_keys(public_keys),
+    );
+
     let mut tasks = ctx.tasks.lock().await;
     tasks.insert(task_index, task.clone());
-    let time_to_expiry =
-        std::time::Duration::from_secs((TASK_CHALLENGE_WINDOW_BLOCK * BLOCK_TIME_SECONDS).into());
+    // A task of a batch auction market is only answered once its window has closed
+    let time_to_expiry = std::time::Duration::from_secs(
+        ((TASK_CHALLENGE_WINDOW_BLOCK + *BATCH_WINDOW_BLOCKS) * BLOCK_TIME_SECONDS).into(),
+    );
 
     if let Some(service) = &ctx.bls_aggregation_service {
         service
@@ -55,6 +104,60 @@ pub async fn initialize_bls_task(
     Ok(1)
 }
 
+/// The operators of each quorum of the task and their stake, at `taskCreatedBlock`.
+async fn operator_stakes(
+    ctx: &AggregatorContext,
+    task: &Task,
+) -> Result<Vec<Vec<(OperatorId, U256)>>, Error> {
+    let operators = ctx
+        .eigenlayer_client()
+        .await
+        .map_err(|e| Error::Context(e.to_string()))?
+        .avs_registry_reader()
+        .await
+        .map_err(|e| Error::Context(e.to_string()))?
+        .get_operators_stake_in_quorums_at_block(
+            task.taskCreatedBlock.into(),
+            task.quorumNumbers.clone(),
+        )
+        .await
+        .map_err(|e| Error::Chain(e.to_string()))?;
+
+    Ok(operators
+        .into_iter()
+        .map(|quorum| {
+            quorum
+                .into_iter()
+                .map(|operator| (operator.operatorId, U256::from(operator.stake)))
+                .collect()
+        })
+        .collect())
+}
+
+/// The G2 public key each operator of the task's quorums registered, at `taskCreatedBlock`.
+async fn operator_public_keys(
+    ctx: &AggregatorContext,
+    task: &Task,
+) -> Result<HashMap<OperatorId, BlsG2Point>, Error> {
+    let operators = ctx
+        .avs_registry_service
+        .as_ref()
+        .ok_or_else(|| Error::Context("AVS Registry Service not initialized".to_string()))?
+        .get_operators_avs_state_at_block(task.taskCreatedBlock, task.quorumNumbers.as_ref())
+        .await
+        .map_err(|e| Error::Chain(e.to_string()))?;
+
+    Ok(operators
+        .into_iter()
+        .filter_map(|(operator_id, operator)| {
+            operator
+                .operator_info
+                .pub_keys
+                .map(|pub_keys| (operator_id, pub_keys.g2_pub_key))
+        })
+        .collect())
+}
+
 /// Converts the event to inputs.
 ///
 /// Uses a tuple to represent the return type because
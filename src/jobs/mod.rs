@@ -1,2 +1,4 @@
 pub mod initialize_task;
-pub mod create_order;
\ No newline at end of file
+pub mod create_order;
+pub mod amend_order;
+pub mod trigger_orders;
\ No newline at end of file
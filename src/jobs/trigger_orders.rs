@@ -0,0 +1,144 @@
+use crate::contexts::aggregator::AggregatorContext;
+use crate::jobs::create_order::orders_at;
+use crate::matching::trigger::traded_prices;
+use crate::IOrderBookTaskManager::TaskResponse;
+use crate::{Error, OrderBookTaskManager, ProcessorError, ORDER_BOOK_TASK_MANAGER_ABI_STRING};
+use alloy_network::{Ethereum, NetworkWallet};
+use alloy_primitives::{Address, U256};
+use blueprint_sdk::eigensdk::common::get_provider;
+use blueprint_sdk::event_listeners::evm::EvmContractEventListener;
+use blueprint_sdk::logging::{error, info, warn};
+use blueprint_sdk::macros::job;
+use std::convert::Infallible;
+
+/// Fires the stop and take-profit orders crossed by the trades of a response.
+///
+/// This job is triggered by the `TaskResponded` event emitted by the `OrderBookTaskManager`
+/// once a response has been accepted on chain. A dormant incoming order is tracked from the
+/// response to its own task, or read back from the contract at the response's block for the
+/// other tasks answered by a batch, then the prices the response traded at update the last
+/// price of each market, see [`traded_prices`]. Every dormant order crossed is submitted to
+/// `triggerOrders`, which replaces it with a live copy matched as a new task.
+///
+/// The contract checks each order against the last price it recorded itself and skips the
+/// ones it has not crossed, so the dormant orders are read back once submitted and those
+/// still dormant are tracked again.
+/// The job returns the number of orders submitted.
+#[job(
+    id = 4,
+    params(task_response, task_manager, block),
+    event_listener(
+        listener = EvmContractEventListener<AggregatorContext, OrderBookTaskManager::TaskResponded>,
+        instance = OrderBookTaskManager,
+        abi = ORDER_BOOK_TASK_MANAGER_ABI_STRING,
+        pre_processor = convert_event_to_inputs,
+    ),
+)]
+pub async fn trigger_orders(
+    ctx: AggregatorContext,
+    task_response: TaskResponse,
+    task_manager: Address,
+    block: u64,
+) -> Result<u32, Infallible> {
+    let task_index = task_response.referenceTaskIndex;
+    let Some(task) = ctx.tasks.lock().await.get(&task_index).cloned() else {
+        warn!("Ignoring response to unknown task {}", task_index);
+        return Ok(0);
+    };
+
+    let provider = get_provider(&ctx.http_rpc_url);
+    let contract = OrderBookTaskManager::new(task_manager, provider);
+    let batch_orders = if task_response.batchTasks.is_empty() {
+        Vec::new()
+    } else {
+        match orders_at(&ctx.http_rpc_url, task_manager, block).await {
+            Ok(orders) => orders,
+            Err(e) => {
+                error!(
+                    "Failed to read the orders of the batch of task {}: {}",
+                    task_index, e
+                );
+                Vec::new()
+            }
+        }
+    };
+    // Fills are priced from the book the response was matched against
+    let traded_book = if task_response.isBatch
+        || (task_response.fills.is_empty() && task_response.ringUpdates.is_empty())
+    {
+        Vec::new()
+    } else {
+        let task_block = task.taskCreatedBlock.into();
+        match orders_at(&ctx.http_rpc_url, task_manager, task_block).await {
+            Ok(orders) => orders,
+            Err(e) => {
+                error!("Failed to read the orderbook of task {}: {}", task_index, e);
+                return Ok(0);
+            }
+        }
+    };
+
+    let mut triggers = ctx.triggers.lock().await;
+    // The stored order is only final once its own task has been answered
+    if triggers.insert(task_index as usize, &task_response.newOrder) {
+        info!("Tracking dormant order {}", task_index);
+    }
+    for index in task_response.batchTasks.iter().map(|index| *index as usize) {
+        if let Some(order) = batch_orders.get(index) {
+            if triggers.insert(index, order) {
+                info!("Tracking dormant order {}", index);
+            }
+        }
+    }
+    let mut fired = Vec::new();
+    for (market, price) in traded_prices(&task, &traded_book, &task_response) {
+        info!("Last price in {}: {}", market, price.to_decimal_string(6));
+        fired.extend(triggers.update(market, price));
+    }
+    drop(triggers);
+
+    if fired.is_empty() {
+        return Ok(0);
+    }
+
+    info!("Triggering orders: {:?}", fired);
+    let count = fired.len() as u32;
+    let submitted = contract
+        .triggerOrders(
+            fired.into_iter().map(U256::from).collect(),
+            task.quorumThresholdPercentage,
+            task.quorumNumbers,
+        )
+        .from(NetworkWallet::<Ethereum>::default_signer_address(
+            &ctx.wallet,
+        ))
+        .send()
+        .await;
+    let submitted = match submitted {
+        Ok(pending) => pending.get_receipt().await.map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = ctx.track_dormant_orders().await {
+        error!("Failed to read back the dormant orders: {}", e);
+    }
+    if let Err(e) = submitted {
+        error!("Failed to trigger orders: {}", e);
+        return Ok(0);
+    }
+
+    Ok(count)
+}
+
+/// Converts the event to inputs.
+///
+/// Uses a tuple to represent the return type because
+/// the macro will index all values in the #[job] function
+/// and parse the return type by the index.
+pub async fn convert_event_to_inputs(
+    (event, log): (OrderBookTaskManager::TaskResponded, alloy_rpc_types::Log),
+) -> Result<Option<(TaskResponse, Address, u64)>, ProcessorError> {
+    let block = log
+        .block_number
+        .ok_or_else(|| Error::Conversion("TaskResponded log has no block number".to_string()))?;
+    Ok(Some((event.taskResponse, log.address(), block)))
+}
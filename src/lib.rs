@@ -1,14 +1,24 @@
 #![allow(dead_code)]
 
+use crate::contexts::aggregator::CacheError;
+use crate::matching::book::BookError;
+use crate::matching::error::MatchError;
+use crate::quorum::SignatureError;
 use alloy_sol_types::sol;
 use blueprint_sdk::macros::load_abi;
 use serde::{Deserialize, Serialize};
 use std::net::AddrParseError;
 use thiserror::Error;
 
+pub mod accounting;
 pub mod constants;
 pub mod contexts;
 pub mod jobs;
+pub mod matching;
+pub mod quorum;
+pub mod store;
+pub mod tokens;
+pub mod twap;
 
 #[cfg(test)]
 mod tests;
@@ -29,6 +39,18 @@ pub enum Error {
     Processor(String),
     #[error("Runtime error: {0}")]
     Runtime(String),
+    #[error("Config error: {0}")]
+    Config(String),
+    #[error("Storage error: {0}")]
+    Storage(String),
+    #[error("Matching error: {0}")]
+    Matching(#[from] MatchError),
+    #[error("Orderbook error: {0}")]
+    Book(#[from] BookError),
+    #[error("Signature error: {0}")]
+    Signature(#[from] SignatureError),
+    #[error("Response cache error: {0}")]
+    Cache(#[from] CacheError),
 }
 
 type ProcessorError =
@@ -65,4 +87,14 @@ sol!(
     #[derive(Debug)]
     PauserRegistry,
     "./contracts/out/IPauserRegistry.sol/IPauserRegistry.json"
-);
\ No newline at end of file
+);
+
+sol!(
+    #[allow(missing_docs)]
+    #[sol(rpc)]
+    #[derive(Debug)]
+    interface IERC20Metadata {
+        function decimals() external view returns (uint8);
+        function symbol() external view returns (string memory);
+    }
+);
@@ -1,22 +1,30 @@
 use alloy_network::EthereumWallet;
 use alloy_primitives::Address;
 use alloy_signer_local::PrivateKeySigner;
-use blueprint_sdk::logging::{info, warn};
+use blueprint_sdk::logging::{error, info};
 use blueprint_sdk::runners::core::runner::BlueprintRunner;
 use blueprint_sdk::runners::eigenlayer::bls::EigenlayerBLSConfig;
 use blueprint_sdk::utils::evm::get_wallet_provider_http;
 use ob_avs::constants::{
-    AGGREGATOR_PRIVATE_KEY, TASK_MANAGER_ADDRESS,
+    AGGREGATOR_PRIVATE_KEY, BATCH_STATE_PATH, TASK_MANAGER_ADDRESS, TOKEN_METADATA, TWAP_ORDERS,
+    TWAP_STATE_PATH,
 };
-use blueprint_sdk::alloy::primitives::{address, U256};
+use blueprint_sdk::alloy::primitives::address;
 
 use ob_avs::contexts::aggregator::AggregatorContext;
 use ob_avs::contexts::client::AggregatorClient;
 use ob_avs::contexts::order::EigenOrderContext;
-use ob_avs::jobs::create_order::OrderEigenEventHandler;
+use ob_avs::jobs::amend_order::AmendOrderEventHandler;
+use ob_avs::jobs::create_order::{resume_batches, OrderEigenEventHandler};
 use ob_avs::jobs::initialize_task::InitializeBlsTaskEventHandler;
+use ob_avs::jobs::trigger_orders::TriggerOrdersEventHandler;
+use ob_avs::matching::auction::{BatchQueue, BatchStore};
+use ob_avs::matching::engine::MatchingConfig;
+use ob_avs::twap::{TwapScheduler, TwapStore};
 use ob_avs::OrderBookTaskManager;
-use blueprint_sdk::utils::evm::get_provider_http;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
 
 #[blueprint_sdk::main(env)]
 async fn main() {
@@ -29,6 +37,13 @@ async fn main() {
     let server_address = format!("{}:{}", "127.0.0.1", 8081);
     let eigen_order_context = EigenOrderContext {
         client: AggregatorClient::new(&server_address)?,
+        matching: MatchingConfig::from_env(),
+        fees: Default::default(),
+        batches: Arc::new(Mutex::new(
+            BatchQueue::load(BatchStore::new(BATCH_STATE_PATH.as_str()))
+                .expect("failed to load the pending batch auctions"),
+        )),
+        tokens: Arc::new(Mutex::new(TOKEN_METADATA.clone())),
         std_config: env.clone(),
     };
 
@@ -47,27 +62,32 @@ async fn main() {
 
     let initialize_task =
         InitializeBlsTaskEventHandler::new(contract.clone(), aggregator_context.clone());
+    let trigger_orders =
+        TriggerOrdersEventHandler::new(contract.clone(), aggregator_context.clone());
 
+    let amend_order = AmendOrderEventHandler::new(contract.clone(), eigen_order_context.clone());
+    resume_batches(&eigen_order_context, *TASK_MANAGER_ADDRESS).await;
     let create_order = OrderEigenEventHandler::new(contract.clone(), eigen_order_context);
-    let rpc_endpoint = env.http_rpc_endpoint.clone();    
-    info!("Spawning a task to create a task on the contract...");
-    blueprint_sdk::tokio::spawn(async move {                
-        let provider = get_provider_http(&rpc_endpoint);
-        let contract_task_generator = OrderBookTaskManager::new(*TASK_MANAGER_ADDRESS, provider);
+    let rpc_endpoint = env.http_rpc_endpoint.clone();
+    info!("Spawning the TWAP scheduler to create tasks on the contract...");
+    blueprint_sdk::tokio::spawn(async move {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("System time is before the unix epoch")
+            .as_secs();
+        let store = TwapStore::new(TWAP_STATE_PATH.as_str());
+        let scheduler = match TwapScheduler::load_or_start(store, &TWAP_ORDERS, now) {
+            Ok(scheduler) => scheduler,
+            Err(e) => {
+                error!("Failed to start the TWAP scheduler: {}", e);
+                return;
+            }
+        };
 
         // We use the Anvil Account #4 as the Task generator address
-        for _ in 1..3 {
-            blueprint_sdk::tokio::time::sleep(std::time::Duration::from_secs(5)).await;
-
-            let task = contract_task_generator
-                .createNewTask(U256::from(5), U256::from(200), address!("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"), address!("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"), U256::from(5), 0, vec![].into())
-                .from(address!("15d34AAf54267DB7D7c367839AAf71A00a2C6A65"));
-            let receipt = task.send().await.unwrap().get_receipt().await.unwrap();
-            if receipt.status() {
-                info!("Task created successfully");
-            } else {
-                warn!("Task creation failed: {:?}", receipt);                            
-            }
+        let generator = address!("15d34AAf54267DB7D7c367839AAf71A00a2C6A65");
+        if let Err(e) = scheduler.run(&rpc_endpoint, *TASK_MANAGER_ADDRESS, generator).await {
+            error!("TWAP scheduler failed: {}", e);
         }
     });
 
@@ -75,7 +95,9 @@ async fn main() {
     let eigen_config = EigenlayerBLSConfig::new(Address::default(), Address::default());
     BlueprintRunner::new(eigen_config, env)
         .job(create_order)
+        .job(amend_order)
         .job(initialize_task)
+        .job(trigger_orders)
         .background_service(Box::new(aggregator_context))
         .run()
         .await?;
@@ -0,0 +1,182 @@
+use crate::matching::iceberg::is_iceberg;
+use crate::IOrderBookTaskManager::Order;
+use alloy_primitives::{U256, U512};
+use thiserror::Error;
+
+/// Why an amendment cannot be applied to an order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum AmendError {
+    #[error("amended order has a zero amount")]
+    ZeroAmount,
+    #[error("order is no longer live")]
+    NotLive,
+}
+
+/// Whether an amended order kept its place in the queue at its price level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    Kept,
+    Reset,
+}
+
+/// Replaces the remaining amounts of a live order.
+///
+/// An amendment that only reduces the size of the order at the same price keeps its
+/// time priority. Any other amendment, a price change or a size increase, resets the
+/// order's `timestamp` to `now`, sending it to the back of the queue as if it had been
+/// cancelled and replaced. The contract applies the same rule in `amendOrder`.
+///
+/// An iceberg order keeps what is left of its slice, never more than its new size, unless
+/// its priority is reset: it then shows a fresh slice like a new order.
+pub fn amend(
+    order: &Order,
+    amount_owned: U256,
+    amount_not_owned: U256,
+    now: U256,
+) -> Result<(Order, Priority), AmendError> {
+    if order.isFilled || order.isCancelled {
+        return Err(AmendError::NotLive);
+    }
+    if amount_owned.is_zero() || amount_not_owned.is_zero() {
+        return Err(AmendError::ZeroAmount);
+    }
+
+    // Same price: amount_owned / amount_not_owned == old_owned / old_not_owned
+    let same_price = U512::from(amount_owned) * U512::from(order.amount_not_owned)
+        == U512::from(amount_not_owned) * U512::from(order.amount_owned);
+    let priority = if same_price && amount_owned <= order.amount_owned {
+        Priority::Kept
+    } else {
+        Priority::Reset
+    };
+
+    let mut amended = order.clone();
+    amended.amount_owned = amount_owned;
+    amended.amount_not_owned = amount_not_owned;
+    if priority == Priority::Reset {
+        amended.timestamp = now;
+    }
+    if is_iceberg(&amended) {
+        amended.displayedAmount = match priority {
+            Priority::Kept => amended.displayedAmount.min(amount_owned),
+            Priority::Reset => amended.displayQuantity.min(amount_owned),
+        };
+    }
+    Ok((amended, priority))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::engine::{match_order, MatchResult};
+    use crate::matching::test_support::{config, order, USDC, WETH};
+
+    fn first_fill(result: &MatchResult) -> usize {
+        result
+            .fills
+            .first()
+            .expect("incoming order was not filled")
+            .index
+    }
+
+    /// Two resting orders selling USDC for WETH at the same price, the first one older.
+    fn book() -> Vec<Order> {
+        vec![
+            Order {
+                timestamp: U256::from(1),
+                ..order(1, USDC, 20, WETH, 20, 30)
+            },
+            Order {
+                timestamp: U256::from(2),
+                ..order(2, USDC, 20, WETH, 20, 30)
+            },
+        ]
+    }
+
+    #[test]
+    fn test_size_down_keeps_priority() {
+        let mut book = book();
+        let (amended, priority) =
+            amend(&book[0], U256::from(10), U256::from(10), U256::from(9)).unwrap();
+        assert_eq!(priority, Priority::Kept);
+        assert_eq!(amended.timestamp, U256::from(1));
+        book[0] = amended;
+
+        let result = match_order(&order(3, WETH, 5, USDC, 5, 30), &book, &config()).unwrap();
+        assert_eq!(first_fill(&result), 0);
+    }
+
+    #[test]
+    fn test_size_up_resets_priority() {
+        let mut book = book();
+        let (amended, priority) =
+            amend(&book[0], U256::from(30), U256::from(30), U256::from(9)).unwrap();
+        assert_eq!(priority, Priority::Reset);
+        assert_eq!(amended.timestamp, U256::from(9));
+        book[0] = amended;
+
+        let result = match_order(&order(3, WETH, 5, USDC, 5, 30), &book, &config()).unwrap();
+        assert_eq!(first_fill(&result), 1);
+    }
+
+    #[test]
+    fn test_price_change_resets_priority() {
+        // The first order starts at a worse price and is re-quoted to the second's
+        let mut book = book();
+        book[0].amount_not_owned = U256::from(22);
+        let (amended, priority) =
+            amend(&book[0], U256::from(20), U256::from(20), U256::from(9)).unwrap();
+        assert_eq!(priority, Priority::Reset);
+        book[0] = amended;
+
+        let result = match_order(&order(3, WETH, 5, USDC, 5, 30), &book, &config()).unwrap();
+        assert_eq!(first_fill(&result), 1);
+
+        // A smaller size at a different price is still a price change
+        let (_, priority) = amend(&book[1], U256::from(10), U256::from(11), U256::from(9)).unwrap();
+        assert_eq!(priority, Priority::Reset);
+    }
+
+    #[test]
+    fn test_iceberg_slice_follows_the_amendment() {
+        let mut iceberg = order(1, USDC, 100, WETH, 100, 30);
+        iceberg.displayQuantity = U256::from(30);
+        iceberg.displayedAmount = U256::from(25);
+
+        // Shrinking below the slice clamps it
+        let (amended, priority) =
+            amend(&iceberg, U256::from(20), U256::from(20), U256::from(9)).unwrap();
+        assert_eq!(priority, Priority::Kept);
+        assert_eq!(amended.displayedAmount, U256::from(20));
+
+        // A size-down above the slice leaves what is left of it
+        let (amended, _) = amend(&iceberg, U256::from(50), U256::from(50), U256::from(9)).unwrap();
+        assert_eq!(amended.displayedAmount, U256::from(25));
+
+        // A reset shows a fresh slice
+        let (amended, priority) =
+            amend(&iceberg, U256::from(200), U256::from(200), U256::from(9)).unwrap();
+        assert_eq!(priority, Priority::Reset);
+        assert_eq!(amended.displayedAmount, U256::from(30));
+    }
+
+    #[test]
+    fn test_invalid_amendments() {
+        let mut filled = order(1, USDC, 20, WETH, 20, 30);
+        filled.isFilled = true;
+        assert_eq!(
+            amend(&filled, U256::from(1), U256::from(1), U256::ZERO).unwrap_err(),
+            AmendError::NotLive
+        );
+        assert_eq!(
+            amend(
+                &order(1, USDC, 20, WETH, 20, 30),
+                U256::ZERO,
+                U256::from(1),
+                U256::ZERO
+            )
+            .unwrap_err(),
+            AmendError::ZeroAmount
+        );
+    }
+}
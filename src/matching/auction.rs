@@ -0,0 +1,718 @@
+use crate::matching::error::{add, sub, MatchError};
+use crate::matching::expiry::is_expired;
+use crate::matching::iceberg::{consume, visible};
+use crate::matching::market::{Market, Side};
+use crate::matching::order_type::OrderType;
+use crate::matching::price::Price;
+use crate::matching::spec::MarketSpecs;
+use crate::matching::trigger::is_dormant;
+use crate::store::JsonStore;
+use crate::Error;
+use crate::IOrderBookTaskManager::Order;
+use alloy_primitives::U256;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+
+/// Markets matched in frequent batch auctions instead of continuously.
+#[derive(Debug, Clone, Default)]
+pub struct BatchMarkets(HashSet<Market>);
+
+impl BatchMarkets {
+    pub fn contains(&self, market: &Market) -> bool {
+        self.0.contains(market)
+    }
+
+    pub fn insert(&mut self, market: Market) {
+        self.0.insert(market);
+    }
+}
+
+/// Parses a comma separated list of `token_a:token_b` pairs.
+impl FromStr for BatchMarkets {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut markets = BatchMarkets::default();
+
+        for entry in s
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let invalid = || Error::Config(format!("Invalid batch auction market: {}", entry));
+            let (token_a, token_b) = entry.split_once(':').ok_or_else(invalid)?;
+            let market = Market::new(
+                token_a.trim().parse().map_err(|_| invalid())?,
+                token_b.trim().parse().map_err(|_| invalid())?,
+            )
+            .ok_or_else(invalid)?;
+            markets.insert(market);
+        }
+
+        Ok(markets)
+    }
+}
+
+/// The batch window a block belongs to.
+pub fn window_of(block: u32, window_blocks: u32) -> u64 {
+    u64::from(block) / u64::from(window_blocks.max(1))
+}
+
+/// The first block of `window`.
+pub fn first_block(window: u64, window_blocks: u32) -> u64 {
+    window * u64::from(window_blocks.max(1))
+}
+
+/// The first block after `window`, from which its batch can be settled.
+pub fn settlement_block(window: u64, window_blocks: u32) -> u64 {
+    first_block(window + 1, window_blocks)
+}
+
+/// The tasks created in a batch window, read from the contract.
+///
+/// A task belongs to the window of the block it was created in, so every operator
+/// reading the `NewTaskCreated` events of the window's blocks agrees on the window's
+/// tasks, whenever it received them. Every order of the window, and every order resting
+/// from earlier windows, comes before the latest task's order in the contract, so the
+/// batch is settled in the response to the latest task. The same response answers the
+/// other tasks of the window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowTasks(Vec<u32>);
+
+impl WindowTasks {
+    /// The tasks of `market` among the tasks `created` in the window, with their orders
+    /// as they were created.
+    ///
+    /// Orders rejected by the specification of their market are answered right away
+    /// instead of being queued, so they are not part of the window.
+    pub fn read<'a>(
+        market: &Market,
+        created: impl IntoIterator<Item = (u32, &'a Order)>,
+        specs: &MarketSpecs,
+    ) -> Self {
+        let mut tasks: Vec<u32> = created
+            .into_iter()
+            .filter(|(_, order)| {
+                Market::of(order).as_ref() == Some(market) && specs.validate(order).is_ok()
+            })
+            .map(|(task_index, _)| task_index)
+            .collect();
+        tasks.sort_unstable();
+        tasks.dedup();
+        Self(tasks)
+    }
+
+    /// The latest task of the window, whose response settles the batch.
+    pub fn task_index(&self) -> Option<u32> {
+        self.0.last().copied()
+    }
+
+    /// The other tasks of the window, lowest first.
+    pub fn earlier_tasks(&self) -> Vec<u32> {
+        self.0[..self.0.len().saturating_sub(1)].to_vec()
+    }
+}
+
+/// A batch window with at least one task that has not been settled yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingBatch {
+    pub market: Market,
+    pub window: u64,
+}
+
+/// Local file the batch queue is persisted to.
+pub type BatchStore = JsonStore<BatchQueue>;
+
+/// Batch windows waiting to be settled, per market.
+///
+/// Only the windows are tracked locally, which tasks each one settles is read from the
+/// contract once it has closed, see [`WindowTasks`]. With a store, every change is
+/// persisted so pending batches are resumed after a restart.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BatchQueue {
+    pending: Vec<PendingBatch>,
+    #[serde(skip)]
+    store: Option<BatchStore>,
+}
+
+impl BatchQueue {
+    /// Resumes the batches persisted in `store` and persists every later change to it.
+    pub fn load(store: BatchStore) -> Result<Self, Error> {
+        let mut queue = store.load()?;
+        queue.store = Some(store);
+        Ok(queue)
+    }
+
+    /// Queues the batch of `market` for `window`. Returns true if the window was not
+    /// pending yet, its settlement has to be scheduled.
+    pub fn add(&mut self, market: Market, window: u64) -> Result<bool, Error> {
+        let batch = PendingBatch { market, window };
+        if self.pending.contains(&batch) {
+            return Ok(false);
+        }
+        self.pending.push(batch);
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Drops the batch of `market` for `window` once it has been settled.
+    pub fn settled(&mut self, market: &Market, window: u64) -> Result<(), Error> {
+        let batch = PendingBatch {
+            market: *market,
+            window,
+        };
+        if let Some(position) = self.pending.iter().position(|pending| *pending == batch) {
+            self.pending.remove(position);
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Every batch that has not been settled yet.
+    pub fn pending(&self) -> impl Iterator<Item = &PendingBatch> {
+        self.pending.iter()
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        match &self.store {
+            Some(store) => store.save(self),
+            None => Ok(()),
+        }
+    }
+}
+
+/// An order's part in a batch auction.
+#[derive(Debug, Clone)]
+pub struct BatchFill {
+    /// Index of the order in the orderbook.
+    pub index: usize,
+    /// The order after the auction.
+    pub new_order: Order,
+    /// Amount of `token_owned` the order gave.
+    pub given: U256,
+    /// Amount of `token_not_owned` the order received.
+    pub received: U256,
+}
+
+/// The outcome of a batch auction in a single market.
+#[derive(Debug, Clone, Default)]
+pub struct AuctionResult {
+    /// Uniform price every fill executed at, in `token1` per `token0` base unit.
+    pub clearing_price: Option<Price>,
+    /// Amount of `token0` traded.
+    pub volume: U256,
+    /// Every order changed by the auction, traded or cancelled, by index.
+    pub updates: Vec<BatchFill>,
+    /// Indices of orders skipped because they had expired.
+    pub expired: Vec<usize>,
+}
+
+impl AuctionResult {
+    /// Conservation check: both tokens given by one side of the market are exactly
+    /// what the other side receives, and every order gave what its update says.
+    pub fn is_balanced(&self, market: &Market, orderbook: &[Order]) -> bool {
+        let mut given = [U256::ZERO; 2];
+        let mut received = [U256::ZERO; 2];
+
+        for update in &self.updates {
+            let Some(order) = orderbook.get(update.index) else {
+                return false;
+            };
+            let side = match market.side(order) {
+                Some(Side::Bid) => 0,
+                Some(Side::Ask) => 1,
+                None => return false,
+            };
+            if order
+                .amount_owned
+                .checked_sub(update.new_order.amount_owned)
+                != Some(update.given)
+            {
+                return false;
+            }
+            let (Some(total_given), Some(total_received)) = (
+                given[side].checked_add(update.given),
+                received[side].checked_add(update.received),
+            ) else {
+                return false;
+            };
+            given[side] = total_given;
+            received[side] = total_received;
+        }
+
+        given[0] == received[1] && given[1] == received[0]
+    }
+}
+
+/// An order taking part in the auction.
+struct Participant {
+    index: usize,
+    /// Limit price in `token1` per `token0`, `None` for market orders.
+    limit: Option<Price>,
+    timestamp: U256,
+}
+
+/// Clears every live order of `market` in a single batch at a uniform price.
+///
+/// The clearing price is the limit price that maximizes the traded volume, then
+/// minimizes the imbalance between demand and supply, then is the lowest. The
+/// short side of the market is filled completely and the long side is filled in
+/// price-time priority. Orders are paired up in priority order and every pair
+/// trades at the clearing price, the buyer's payment rounded up, so both legs of
+/// every trade balance exactly.
+///
+/// An ask clearing above its limit receives more than it asked for; its remaining
+/// `amount_not_owned` drops to zero rather than below. Orders that cannot rest are
+/// cancelled once the auction is over, and fill-or-kill orders are not supported in
+/// batch markets and are cancelled without trading. Orders of the same user may
+/// trade with each other at the clearing price. Iceberg orders take part with their
+/// displayed slice only and the slice is replenished afterwards, see [`consume`].
+pub fn clear(
+    market: Market,
+    orderbook: &[Order],
+    timestamp: U256,
+) -> Result<AuctionResult, MatchError> {
+    let mut result = AuctionResult::default();
+    let mut bids = Vec::new();
+    let mut asks = Vec::new();
+    let mut cancelled = Vec::new();
+
+    for (index, order) in orderbook.iter().enumerate() {
+        // Dormant orders wait for their trigger, even in a batch market
+        if order.isFilled || order.isCancelled || is_dormant(order) {
+            continue;
+        }
+        let Some(side) = market.side(order) else {
+            continue;
+        };
+        if is_expired(order, timestamp) {
+            result.expired.push(index);
+            continue;
+        }
+        let Some(price) = Price::of(order) else {
+            continue;
+        };
+        let order_type = match OrderType::of(order) {
+            Some(OrderType::FillOrKill) | None => {
+                cancelled.push(index);
+                continue;
+            }
+            Some(order_type) => order_type,
+        };
+        if !order_type.can_rest() {
+            cancelled.push(index);
+        }
+
+        // Both sides are quoted in `token1` per `token0`
+        let limit = order_type.has_limit_price().then(|| match side {
+            Side::Bid => price,
+            Side::Ask => price.inverse(),
+        });
+        let participant = Participant {
+            index,
+            limit,
+            timestamp: order.timestamp,
+        };
+        match side {
+            Side::Bid => bids.push(participant),
+            Side::Ask => asks.push(participant),
+        }
+    }
+
+    // Market orders first, then best price, then oldest, then lowest index
+    let priority = |a: &Participant, b: &Participant, better: Ordering| match (a.limit, b.limit) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Less,
+        (Some(_), None) => Ordering::Greater,
+        (Some(a), Some(b)) => {
+            if a.cmp(&b) == better {
+                Ordering::Less
+            } else if a == b {
+                Ordering::Equal
+            } else {
+                Ordering::Greater
+            }
+        }
+    };
+    bids.sort_by(|a, b| {
+        priority(a, b, Ordering::Greater)
+            .then(a.timestamp.cmp(&b.timestamp))
+            .then(a.index.cmp(&b.index))
+    });
+    asks.sort_by(|a, b| {
+        priority(a, b, Ordering::Less)
+            .then(a.timestamp.cmp(&b.timestamp))
+            .then(a.index.cmp(&b.index))
+    });
+
+    let mut prices: Vec<Price> = bids
+        .iter()
+        .chain(&asks)
+        .filter_map(|participant| participant.limit)
+        .collect();
+    prices.sort();
+    prices.dedup();
+
+    let mut best: Option<(Price, U256, U256)> = None;
+    for price in prices {
+        let demand = bids
+            .iter()
+            .filter(|bid| bid.limit.map_or(true, |limit| limit >= price))
+            .try_fold(U256::ZERO, |sum, bid| {
+                add(sum, demand_of(&orderbook[bid.index], price)?, "demand")
+            })?;
+        let supply = asks
+            .iter()
+            .filter(|ask| ask.limit.map_or(true, |limit| limit <= price))
+            .try_fold(U256::ZERO, |sum, ask| {
+                add(sum, visible(&orderbook[ask.index]), "supply")
+            })?;
+        let volume = demand.min(supply);
+        let imbalance = demand.max(supply) - volume;
+
+        let better = match best {
+            None => !volume.is_zero(),
+            Some((_, best_volume, best_imbalance)) => {
+                volume > best_volume || (volume == best_volume && imbalance < best_imbalance)
+            }
+        };
+        if better {
+            best = Some((price, volume, imbalance));
+        }
+    }
+
+    let mut legs: BTreeMap<usize, (U256, U256)> = BTreeMap::new();
+    if let Some((price, volume, _)) = best {
+        result.clearing_price = Some(price);
+        result.volume = volume;
+
+        let allocate = |participants: &[Participant],
+                        size: &dyn Fn(&Order) -> Result<U256, MatchError>,
+                        crosses: &dyn Fn(Price) -> bool|
+         -> Result<Vec<(usize, U256)>, MatchError> {
+            let mut left = volume;
+            let mut allocations = Vec::new();
+            for participant in participants {
+                if left.is_zero() {
+                    break;
+                }
+                if !participant.limit.map_or(true, crosses) {
+                    continue;
+                }
+                let amount = size(&orderbook[participant.index])?.min(left);
+                if !amount.is_zero() {
+                    left = sub(left, amount, "auction allocation")?;
+                    allocations.push((participant.index, amount));
+                }
+            }
+            Ok(allocations)
+        };
+        let bid_allocations = allocate(&bids, &|order| demand_of(order, price), &|limit| {
+            limit >= price
+        })?;
+        let ask_allocations =
+            allocate(&asks, &|order| Ok(visible(order)), &|limit| limit <= price)?;
+
+        // Pair bids and asks up in priority order, every pair trades at the clearing price
+        let mut budgets: HashMap<usize, U256> = bid_allocations
+            .iter()
+            .map(|(index, _)| (*index, visible(&orderbook[*index])))
+            .collect();
+        let mut bid_iter = bid_allocations.into_iter();
+        let mut ask_iter = ask_allocations.into_iter();
+        let mut bid = bid_iter.next();
+        let mut ask = ask_iter.next();
+        while let (Some((bid_index, bid_left)), Some((ask_index, ask_left))) = (bid, ask) {
+            let amount = bid_left.min(ask_left);
+            let budget = budgets.entry(bid_index).or_default();
+            let paid = price
+                .mul_ceil(amount)
+                .ok_or(MatchError::Overflow("auction payment"))?
+                .min(*budget);
+            *budget = sub(*budget, paid, "auction budget")?;
+
+            let bid_legs = legs.entry(bid_index).or_default();
+            bid_legs.0 = add(bid_legs.0, paid, "auction payment")?;
+            bid_legs.1 = add(bid_legs.1, amount, "auction payment")?;
+            let ask_legs = legs.entry(ask_index).or_default();
+            ask_legs.0 = add(ask_legs.0, amount, "auction payment")?;
+            ask_legs.1 = add(ask_legs.1, paid, "auction payment")?;
+
+            let bid_left = sub(bid_left, amount, "auction allocation")?;
+            let ask_left = sub(ask_left, amount, "auction allocation")?;
+            bid = if bid_left.is_zero() {
+                bid_iter.next()
+            } else {
+                Some((bid_index, bid_left))
+            };
+            ask = if ask_left.is_zero() {
+                ask_iter.next()
+            } else {
+                Some((ask_index, ask_left))
+            };
+        }
+    }
+
+    let mut updates: BTreeMap<usize, BatchFill> = BTreeMap::new();
+    for (index, (given, received)) in legs {
+        let order = &orderbook[index];
+        let mut new_order = order.clone();
+        new_order.amount_owned = sub(order.amount_owned, given, "auction remainder")?;
+        new_order.amount_not_owned = order.amount_not_owned.saturating_sub(received);
+        new_order.timestamp_matched = timestamp;
+        if new_order.amount_owned.is_zero() || new_order.amount_not_owned.is_zero() {
+            new_order.isFilled = true;
+        } else {
+            new_order.isPartiallyFilled = true;
+        }
+        consume(&mut new_order, given, timestamp);
+        updates.insert(
+            index,
+            BatchFill {
+                index,
+                new_order,
+                given,
+                received,
+            },
+        );
+    }
+
+    for index in cancelled {
+        let update = updates.entry(index).or_insert_with(|| BatchFill {
+            index,
+            new_order: orderbook[index].clone(),
+            given: U256::ZERO,
+            received: U256::ZERO,
+        });
+        if !update.new_order.isFilled {
+            update.new_order.isCancelled = true;
+        }
+    }
+
+    result.updates = updates.into_values().collect();
+    Ok(result)
+}
+
+/// Amount of `token0` a bid can buy at `price`, limited by what its displayed slice
+/// can pay.
+fn demand_of(bid: &Order, price: Price) -> Result<U256, MatchError> {
+    let affordable = price
+        .div_floor(visible(bid))
+        .ok_or(MatchError::Overflow("demand"))?;
+    Ok(bid.amount_not_owned.min(affordable))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::test_support::{order, DAI, USDC, WETH};
+
+    fn market() -> Market {
+        Market::new(WETH, USDC).unwrap()
+    }
+
+    /// Buys `size` WETH for up to `price` USDC each.
+    fn bid(size: u64, price: u64) -> Order {
+        order(1, USDC, size * price, WETH, size, 0)
+    }
+
+    /// Sells `size` WETH for at least `price` USDC each.
+    fn ask(size: u64, price: u64) -> Order {
+        order(2, WETH, size, USDC, size * price, 0)
+    }
+
+    fn with_type(mut order: Order, order_type: OrderType) -> Order {
+        order.orderType = order_type.into();
+        order
+    }
+
+    fn update(result: &AuctionResult, index: usize) -> &BatchFill {
+        result
+            .updates
+            .iter()
+            .find(|update| update.index == index)
+            .expect("order was not updated")
+    }
+
+    #[test]
+    fn test_uniform_clearing_price() {
+        let book = vec![
+            bid(10, 12),
+            bid(10, 10),
+            ask(5, 9),
+            ask(10, 11),
+            ask(10, 13),
+        ];
+
+        let result = clear(market(), &book, U256::from(100)).unwrap();
+        // At 11, 10 WETH are demanded and 15 supplied; at 10, 20 demanded and 5 supplied
+        assert_eq!(
+            result.clearing_price,
+            Price::new(U256::from(11), U256::from(1))
+        );
+        assert_eq!(result.volume, U256::from(10));
+        assert!(result.is_balanced(&market(), &book));
+
+        // Every fill is at 11 USDC per WETH, the bid at 12 pays less than its limit
+        let buyer = update(&result, 0);
+        assert_eq!(buyer.received, U256::from(10));
+        assert_eq!(buyer.given, U256::from(110));
+        assert!(buyer.new_order.isFilled);
+        assert_eq!(buyer.new_order.amount_owned, U256::from(10));
+        assert_eq!(buyer.new_order.timestamp_matched, U256::from(100));
+
+        // The cheapest ask is filled first, the next one is rationed
+        let first = update(&result, 2);
+        assert_eq!(first.given, U256::from(5));
+        assert_eq!(first.received, U256::from(55));
+        assert!(first.new_order.isFilled);
+        let second = update(&result, 3);
+        assert_eq!(second.given, U256::from(5));
+        assert_eq!(second.received, U256::from(55));
+        assert!(second.new_order.isPartiallyFilled);
+
+        assert_eq!(result.updates.len(), 3);
+    }
+
+    #[test]
+    fn test_no_trade_when_nothing_crosses() {
+        let book = vec![bid(10, 10), ask(10, 11)];
+        let result = clear(market(), &book, U256::ZERO).unwrap();
+        assert_eq!(result.clearing_price, None);
+        assert!(result.updates.is_empty());
+    }
+
+    #[test]
+    fn test_ties_clear_at_lowest_price() {
+        let book = vec![bid(10, 12), ask(10, 10)];
+        let result = clear(market(), &book, U256::ZERO).unwrap();
+        assert_eq!(
+            result.clearing_price,
+            Price::new(U256::from(10), U256::from(1))
+        );
+        assert_eq!(update(&result, 0).given, U256::from(100));
+        assert!(result.is_balanced(&market(), &book));
+    }
+
+    #[test]
+    fn test_time_priority_within_a_price_level() {
+        let mut older = ask(10, 10);
+        older.timestamp = U256::from(1);
+        let mut newer = ask(10, 10);
+        newer.timestamp = U256::from(2);
+        let book = vec![newer, older, bid(5, 10)];
+
+        let result = clear(market(), &book, U256::ZERO).unwrap();
+        assert_eq!(update(&result, 1).given, U256::from(5));
+        assert!(result.updates.iter().all(|update| update.index != 0));
+    }
+
+    #[test]
+    fn test_market_orders_and_non_resting_remainders() {
+        let book = vec![
+            with_type(order(1, USDC, 200, WETH, 10, 0), OrderType::Market),
+            with_type(ask(4, 10), OrderType::ImmediateOrCancel),
+            ask(20, 12),
+            with_type(bid(1, 100), OrderType::FillOrKill),
+        ];
+
+        let result = clear(market(), &book, U256::ZERO).unwrap();
+        assert!(result.is_balanced(&market(), &book));
+        assert_eq!(
+            result.clearing_price,
+            Price::new(U256::from(12), U256::from(1))
+        );
+
+        // The market bid could afford 16 WETH at 12 but only asked for 10
+        let market_bid = update(&result, 0);
+        assert_eq!(market_bid.received, U256::from(10));
+        assert!(market_bid.new_order.isFilled);
+
+        // The IOC ask is filled, the fill-or-kill bid never trades
+        assert!(update(&result, 1).new_order.isFilled);
+        let fill_or_kill = update(&result, 3);
+        assert!(fill_or_kill.new_order.isCancelled);
+        assert_eq!(fill_or_kill.given, U256::ZERO);
+    }
+
+    #[test]
+    fn test_batch_markets_from_config() {
+        let markets: BatchMarkets = format!("{}:{}", USDC, WETH).parse().unwrap();
+        assert!(markets.contains(&market()));
+        assert!(format!("{}", WETH).parse::<BatchMarkets>().is_err());
+        assert!(format!("{}:{}", WETH, WETH)
+            .parse::<BatchMarkets>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_windows() {
+        assert_eq!(window_of(9, 5), 1);
+        assert_eq!(window_of(10, 5), 2);
+        assert_eq!(first_block(2, 5), 10);
+        assert_eq!(settlement_block(1, 5), 10);
+    }
+
+    #[test]
+    fn test_window_tasks_are_read_from_the_contract() {
+        let specs: MarketSpecs = format!("{}:{}:2:1:1:0", WETH, USDC).parse().unwrap();
+        let orders = [
+            ask(5, 11),
+            order(3, WETH, 5, DAI, 5, 0),
+            // Below the minimum size, answered right away
+            ask(1, 10),
+            bid(5, 12),
+        ];
+        // Tasks are read from the events of the window, in any order
+        let created = [
+            (5, &orders[3]),
+            (2, &orders[0]),
+            (3, &orders[1]),
+            (4, &orders[2]),
+        ];
+
+        let tasks = WindowTasks::read(&market(), created, &specs);
+        assert_eq!(tasks.task_index(), Some(5));
+        assert_eq!(tasks.earlier_tasks(), vec![2]);
+
+        let tasks = WindowTasks::read(&market(), [], &specs);
+        assert_eq!(tasks.task_index(), None);
+        assert!(tasks.earlier_tasks().is_empty());
+    }
+
+    #[test]
+    fn test_queue_opens_each_window_once() {
+        let mut queue = BatchQueue::default();
+        assert!(queue.add(market(), 1).unwrap());
+        assert!(!queue.add(market(), 1).unwrap());
+        assert!(queue.add(market(), 2).unwrap());
+
+        queue.settled(&market(), 1).unwrap();
+        let windows: Vec<u64> = queue.pending().map(|batch| batch.window).collect();
+        assert_eq!(windows, vec![2]);
+    }
+
+    #[test]
+    fn test_pending_batches_survive_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BatchStore::new(dir.path().join("batches.json"));
+        let mut queue = BatchQueue::load(store.clone()).unwrap();
+        queue.add(market(), 1).unwrap();
+        queue.add(market(), 2).unwrap();
+
+        let mut resumed = BatchQueue::load(store.clone()).unwrap();
+        let windows: Vec<u64> = resumed.pending().map(|batch| batch.window).collect();
+        assert_eq!(windows, vec![1, 2]);
+        assert!(!resumed.add(market(), 1).unwrap());
+
+        resumed.settled(&market(), 1).unwrap();
+        let windows: Vec<u64> = BatchQueue::load(store)
+            .unwrap()
+            .pending()
+            .map(|batch| batch.window)
+            .collect();
+        assert_eq!(windows, vec![2]);
+    }
+}
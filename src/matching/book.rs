@@ -0,0 +1,143 @@
+use crate::IOrderBookTaskManager::Order;
+use thiserror::Error;
+
+/// Why the orderbook of a task could not be built from the contract's orders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum BookError {
+    #[error("order {index} of the task is missing, the contract holds {len} order(s)")]
+    MissingOrder { index: usize, len: usize },
+}
+
+/// The orderbook a task is matched against, as held by the contract at the task's block.
+///
+/// Cancellations, amendments and earlier responses are all applied on chain, so every
+/// operator reading the same block sees the same book, whatever order it received the
+/// events in. The snapshot in `NewTaskCreated` is not used, it misses every change made
+/// since the task was created.
+///
+/// Orders that changed from the task's block on are withheld: the contract drops fills
+/// on them, since they may have changed again after the book was read. They are kept at
+/// their index but marked cancelled, so matching skips them.
+#[derive(Debug, Clone)]
+pub struct TaskBook {
+    /// The task's own order.
+    pub order: Order,
+    /// Every order created before the task, at the index of its own task.
+    pub orderbook: Vec<Order>,
+}
+
+impl TaskBook {
+    /// Splits the contract's `orders` into the order of the task at `task_index` and the
+    /// orders created before it. Orders of later tasks are dropped, and orders whose entry
+    /// in `changed_blocks` is at or after `task_block` are withheld.
+    pub fn split(
+        task_index: u32,
+        mut orders: Vec<Order>,
+        changed_blocks: &[u32],
+        task_block: u32,
+    ) -> Result<Self, BookError> {
+        let index = task_index as usize;
+        if index >= orders.len() {
+            return Err(BookError::MissingOrder {
+                index,
+                len: orders.len(),
+            });
+        }
+        orders.truncate(index + 1);
+        let order = orders.pop().expect("the task's order was checked above");
+        for (resting, changed) in orders.iter_mut().zip(changed_blocks) {
+            if *changed >= task_block {
+                resting.isCancelled = true;
+            }
+        }
+
+        Ok(Self {
+            order,
+            orderbook: orders,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::engine::match_order;
+    use crate::matching::test_support::{config, order, USDC, WETH};
+    use alloy_primitives::U256;
+
+    #[test]
+    fn test_split_drops_orders_of_later_tasks() {
+        let orders = vec![
+            order(1, USDC, 10, WETH, 10, 5),
+            order(2, WETH, 10, USDC, 10, 5),
+            order(3, USDC, 10, WETH, 10, 5),
+        ];
+
+        let book = TaskBook::split(1, orders.clone(), &[0; 3], 7).unwrap();
+        assert_eq!(book.order.user, orders[1].user);
+        assert_eq!(book.orderbook.len(), 1);
+        assert_eq!(book.orderbook[0].user, orders[0].user);
+
+        assert_eq!(
+            TaskBook::split(3, orders, &[0; 3], 7).unwrap_err(),
+            BookError::MissingOrder { index: 3, len: 3 }
+        );
+    }
+
+    #[test]
+    fn test_cancelled_orders_are_no_longer_matched() {
+        // The contract cancelled the resting order after the task's snapshot was taken
+        let resting = Order {
+            isCancelled: true,
+            ..order(2, USDC, 10, WETH, 10, 5)
+        };
+        let book = TaskBook::split(
+            1,
+            vec![resting, order(1, WETH, 10, USDC, 10, 5)],
+            &[5, 0],
+            7,
+        )
+        .unwrap();
+
+        let result = match_order(&book.order, &book.orderbook, &config()).unwrap();
+        assert!(!result.is_match());
+    }
+
+    #[test]
+    fn test_orders_changed_from_the_task_block_on_are_withheld() {
+        let orders = vec![
+            order(2, USDC, 10, WETH, 10, 5),
+            order(3, USDC, 10, WETH, 10, 5),
+            order(1, WETH, 20, USDC, 20, 5),
+        ];
+
+        // The first order was amended at the task's block, the second one before it
+        let book = TaskBook::split(2, orders, &[7, 6, 0], 7).unwrap();
+        assert!(book.orderbook[0].isCancelled);
+        assert!(!book.orderbook[1].isCancelled);
+
+        let result = match_order(&book.order, &book.orderbook, &config()).unwrap();
+        assert_eq!(result.fills.len(), 1);
+        assert_eq!(result.fills[0].index, 1);
+    }
+
+    #[test]
+    fn test_amended_orders_match_their_amended_size() {
+        let resting = Order {
+            amount_owned: U256::from(4),
+            amount_not_owned: U256::from(4),
+            ..order(2, USDC, 10, WETH, 10, 5)
+        };
+        let book = TaskBook::split(
+            1,
+            vec![resting, order(1, WETH, 10, USDC, 10, 5)],
+            &[5, 0],
+            7,
+        )
+        .unwrap();
+
+        let result = match_order(&book.order, &book.orderbook, &config()).unwrap();
+        assert_eq!(result.fills.len(), 1);
+        assert_eq!(result.fills[0].amount, U256::from(4));
+    }
+}
@@ -0,0 +1,1447 @@
+use crate::constants::{
+    BATCH_AUCTION_MARKETS, BATCH_WINDOW_BLOCKS, MAKER_FEE_BPS, MARKET_SPECS, MATCHING_STRATEGY,
+    MAX_FILLS_PER_TASK, MAX_RING_LENGTH, SELF_TRADE_PREVENTION, TAKER_FEE_BPS,
+};
+use crate::matching::auction::BatchMarkets;
+use crate::matching::error::{add, sub, MatchError};
+use crate::matching::expiry::is_expired;
+use crate::matching::fees::FeeSchedule;
+use crate::matching::iceberg::{consume, visible};
+use crate::matching::market::is_counterparty;
+use crate::matching::order_type::OrderType;
+use crate::matching::price::Price;
+use crate::matching::ring::{match_rings, ring_totals, Ring, RingBudget};
+use crate::matching::spec::{MarketSpecs, Rejection};
+use crate::matching::stp::SelfTradePrevention;
+use crate::matching::strategy::{Candidate, MatchingStrategy};
+use crate::matching::trigger::is_dormant;
+use crate::IOrderBookTaskManager::Order;
+use alloy_primitives::U256;
+use std::sync::Arc;
+
+/// Deployment-wide settings of the matching engine.
+#[derive(Debug, Clone)]
+pub struct MatchingConfig {
+    /// How an incoming order is allocated across the resting orders it crosses.
+    pub strategy: Arc<dyn MatchingStrategy>,
+    /// Maximum number of resting orders a single incoming order may be filled against.
+    pub max_fills: usize,
+    /// What happens when an incoming order would match an order from the same user.
+    pub self_trade_prevention: SelfTradePrevention,
+    /// Maker and taker fee rates charged on every fill.
+    pub fees: FeeSchedule,
+    /// Granularity rules incoming orders are validated against before matching.
+    pub markets: Arc<MarketSpecs>,
+    /// Markets matched in frequent batch auctions, see [`crate::matching::auction`].
+    pub batch_markets: Arc<BatchMarkets>,
+    /// Number of blocks in a batch auction window.
+    pub batch_window_blocks: u32,
+    /// Maximum number of orders in a ring, including the incoming order. Values below
+    /// three disable ring matching.
+    pub max_ring_length: usize,
+}
+
+impl MatchingConfig {
+    /// Loads the configuration from the environment, see [`crate::constants`].
+    pub fn from_env() -> Self {
+        Self {
+            strategy: MATCHING_STRATEGY.build(),
+            max_fills: *MAX_FILLS_PER_TASK,
+            self_trade_prevention: *SELF_TRADE_PREVENTION,
+            fees: FeeSchedule::new(*MAKER_FEE_BPS, *TAKER_FEE_BPS)
+                .expect("Fee rates must not exceed 10000 bps"),
+            markets: Arc::new(MARKET_SPECS.clone()),
+            batch_markets: Arc::new(BATCH_AUCTION_MARKETS.clone()),
+            batch_window_blocks: *BATCH_WINDOW_BLOCKS,
+            max_ring_length: *MAX_RING_LENGTH,
+        }
+    }
+}
+
+/// What the operators agreed happens to the incoming order of a task.
+///
+/// The discriminant is the code reported in `TaskResponse.outcome`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MatchOutcome {
+    /// Nothing crossed, the incoming order rests in the book unchanged.
+    Resting = 0,
+    /// The incoming order was filled against at least one resting order.
+    Matched = 1,
+    /// Nothing was filled and the incoming order will never rest in the book.
+    Cancelled = 2,
+    /// The incoming order violates the specification of its market.
+    Rejected = 3,
+}
+
+impl From<MatchOutcome> for u8 {
+    fn from(outcome: MatchOutcome) -> Self {
+        outcome as u8
+    }
+}
+
+/// Amounts an order gives up and receives, in its `token_owned` and `token_not_owned`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Legs {
+    pub given: U256,
+    pub received: U256,
+}
+
+/// A single fill of the incoming order against a resting order.
+#[derive(Debug, Clone)]
+pub struct MatchFill {
+    /// Index of the resting order in the orderbook.
+    pub index: usize,
+    /// The resting order after the fill has been applied.
+    pub new_order: Order,
+    /// Amount of `token_not_owned` the incoming order receives from the resting order.
+    pub amount: U256,
+    /// Amount of `token_owned` the incoming order pays to the resting order.
+    pub paid: U256,
+    /// Fee charged to the resting order, out of `paid`.
+    pub maker_fee: U256,
+    /// Fee charged to the incoming order, out of `amount`.
+    pub taker_fee: U256,
+    /// Price the fill executed at, quoted in the same units as the incoming order.
+    pub price: Price,
+}
+
+/// A resting order changed without being filled.
+#[derive(Debug, Clone)]
+pub struct OrderUpdate {
+    /// Index of the resting order in the orderbook.
+    pub index: usize,
+    /// The resting order after the update has been applied.
+    pub new_order: Order,
+}
+
+/// The outcome of matching an incoming order against the resting orderbook.
+#[derive(Debug, Clone)]
+pub struct MatchResult {
+    /// The incoming order after every fill has been applied.
+    pub new_order: Order,
+    /// The resting orders filled against the incoming order, in execution order.
+    pub fills: Vec<MatchFill>,
+    /// Rings of resting orders in other markets the incoming order was filled through,
+    /// only tried when nothing matched it directly.
+    pub rings: Vec<Ring>,
+    /// Indices of resting orders skipped because they had expired.
+    pub expired: Vec<usize>,
+    /// The self-trade prevention mode applied, if the incoming order met its own liquidity.
+    pub self_trade: Option<SelfTradePrevention>,
+    /// Resting orders of the same user cancelled or decremented by self-trade prevention.
+    pub self_trade_updates: Vec<OrderUpdate>,
+    /// Amounts removed from the incoming order by self-trade prevention without trading.
+    pub self_trade_decrement: Legs,
+    /// Why the incoming order was rejected before matching, if it was.
+    pub rejection: Option<Rejection>,
+}
+
+impl MatchResult {
+    fn unmatched(order: &Order) -> Self {
+        Self {
+            new_order: order.clone(),
+            fills: Vec::new(),
+            rings: Vec::new(),
+            expired: Vec::new(),
+            self_trade: None,
+            self_trade_updates: Vec::new(),
+            self_trade_decrement: Legs::default(),
+            rejection: None,
+        }
+    }
+
+    pub fn is_match(&self) -> bool {
+        !self.fills.is_empty() || !self.rings.is_empty()
+    }
+
+    /// Total fee charged to the incoming order, in its `token_not_owned`.
+    pub fn taker_fee(&self) -> U256 {
+        // Each fee is at most the amount it is charged on, so the total never saturates
+        self.fills
+            .iter()
+            .fold(U256::ZERO, |sum, fill| sum.saturating_add(fill.taker_fee))
+    }
+
+    pub fn outcome(&self) -> MatchOutcome {
+        if self.rejection.is_some() {
+            MatchOutcome::Rejected
+        } else if self.is_match() {
+            MatchOutcome::Matched
+        } else if self.new_order.isCancelled {
+            MatchOutcome::Cancelled
+        } else {
+            MatchOutcome::Resting
+        }
+    }
+
+    /// Conservation check: every amount the incoming order gives or receives must be
+    /// exactly what the resting orders receive or give on the other leg of each fill.
+    pub fn is_balanced(&self, order: &Order, orderbook: &[Order]) -> bool {
+        let mut incoming = self.self_trade_decrement;
+
+        for fill in &self.fills {
+            let Some(other_order) = orderbook.get(fill.index) else {
+                return false;
+            };
+            let other_given = other_order
+                .amount_owned
+                .checked_sub(fill.new_order.amount_owned);
+            let other_received = other_order
+                .amount_not_owned
+                .checked_sub(fill.new_order.amount_not_owned);
+            if other_given != Some(fill.amount) || other_received != Some(fill.paid) {
+                return false;
+            }
+
+            let (Some(received), Some(given)) = (
+                incoming.received.checked_add(fill.amount),
+                incoming.given.checked_add(fill.paid),
+            ) else {
+                return false;
+            };
+            incoming = Legs { given, received };
+        }
+
+        // Each ring is settled over the orderbook as updated by the rings before it
+        let mut book = orderbook.to_vec();
+        for ring in &self.rings {
+            if !ring.is_balanced(&book) {
+                return false;
+            }
+            for hop in &ring.hops {
+                book[hop.index] = hop.new_order.clone();
+            }
+            let (Some(received), Some(given)) = (
+                incoming.received.checked_add(ring.received),
+                incoming.given.checked_add(ring.given),
+            ) else {
+                return false;
+            };
+            incoming = Legs { given, received };
+        }
+
+        order.amount_owned.checked_sub(self.new_order.amount_owned) == Some(incoming.given)
+            && order
+                .amount_not_owned
+                .checked_sub(self.new_order.amount_not_owned)
+                == Some(incoming.received)
+    }
+}
+
+/// Matches an incoming order against the resting orders of the orderbook.
+///
+/// Every resting order that crosses the incoming order is ranked in price-time
+/// priority and the configured strategy decides how the incoming order is allocated
+/// across them, using at most `max_fills` resting orders. When the allocation
+/// reaches a resting order from the same user, the configured self-trade prevention
+/// mode is applied before matching continues.
+///
+/// The incoming order's `orderType` decides what happens around the allocation:
+/// market orders ignore their own price but still respect `slippage`, post-only
+/// orders are cancelled if they cross another user's order, fill-or-kill orders
+/// are cancelled unless they fill completely and the unfilled part of any order
+/// that cannot rest is cancelled. A resting iceberg order only offers its displayed slice and
+/// shows a new one at the back of the queue once it runs out, see [`consume`].
+///
+/// When nothing matches the incoming order directly, it may still be filled
+/// through rings of resting orders over three or more tokens, see
+/// [`match_rings`]. Post-only orders and orders that met their own liquidity are
+/// never filled through a ring.
+///
+/// This function is pure: it has no access to the chain or the keystore, so every
+/// operator given the same inputs computes the same result. All arithmetic is
+/// checked, an incoming order that cannot be matched safely is reported as a
+/// [`MatchError`]. Resting orders with a zero amount on either side have no price
+/// and are never matched.
+///
+/// Prices are compared in base units. Every order of a market is quoted in the same
+/// two tokens, so this ranks orders exactly as prices normalized by token decimals
+/// would, see [`Price::normalize`].
+pub fn match_order(
+    order: &Order,
+    orderbook: &[Order],
+    config: &MatchingConfig,
+) -> Result<MatchResult, MatchError> {
+    let mut result = MatchResult::unmatched(order);
+
+    if order.isFilled || order.isCancelled {
+        return Ok(result);
+    }
+
+    if order.token_owned == order.token_not_owned {
+        return Err(MatchError::InvalidPair);
+    }
+
+    let price_for_user = Price::of(order).ok_or(MatchError::ZeroAmount)?;
+
+    let Some(order_type) = OrderType::of(order) else {
+        result.new_order.isCancelled = true;
+        return Ok(result);
+    };
+
+    if let Err(rejection) = config.markets.validate(order) {
+        result.new_order.isCancelled = true;
+        result.rejection = Some(rejection);
+        return Ok(result);
+    }
+
+    // A conditional order rests untouched until its trigger fires and the contract
+    // replaces it with a live copy
+    if is_dormant(order) {
+        return Ok(result);
+    }
+
+    let mut candidates = Vec::new();
+    for (index, other_order) in orderbook.iter().enumerate() {
+        if other_order.isFilled || other_order.isCancelled || is_dormant(other_order) {
+            continue;
+        }
+
+        // Expiry is checked against the incoming order's creation time so every
+        // operator agrees on which orders are still live
+        if is_expired(other_order, order.timestamp) {
+            result.expired.push(index);
+            continue;
+        }
+
+        // Orders that never rest are only in the book until their own task is answered
+        if !OrderType::of(other_order).is_some_and(OrderType::can_rest) {
+            continue;
+        }
+
+        if !is_counterparty(order, other_order) {
+            continue;
+        }
+
+        // The resting order's price, quoted in the same units as the incoming order
+        let Some(execution_price) = Price::of(other_order).map(Price::inverse) else {
+            continue;
+        };
+
+        if order_type.has_limit_price() && execution_price > price_for_user {
+            continue;
+        }
+
+        if price_for_user.deviation_exceeds(execution_price, order.slippage) {
+            continue;
+        }
+
+        candidates.push(Candidate {
+            index,
+            price: execution_price,
+            timestamp: other_order.timestamp,
+            // Only the displayed slice of an iceberg order trades against a single task
+            available: visible(other_order),
+        });
+    }
+
+    // Price-time priority: best price for the incoming order first, then the oldest
+    // resting order, then the lowest index so every operator agrees on the order.
+    candidates.sort_by(|a, b| {
+        a.price
+            .cmp(&b.price)
+            .then(a.timestamp.cmp(&b.timestamp))
+            .then(a.index.cmp(&b.index))
+    });
+
+    // Crossing the user's own orders is left to self-trade prevention, only a crossing
+    // order of another user would make a post-only order take liquidity
+    if order_type == OrderType::PostOnly
+        && candidates
+            .iter()
+            .any(|candidate| orderbook[candidate.index].user != order.user)
+    {
+        result.new_order.isCancelled = true;
+        return Ok(result);
+    }
+
+    // What the incoming order still wants to receive, and can still give
+    let mut remaining = order.amount_not_owned;
+    let mut budget = order.amount_owned;
+    let allocations = loop {
+        let mut allocations =
+            config
+                .strategy
+                .allocate(price_for_user, remaining, &candidates, config.max_fills)?;
+
+        let mut self_trade = None;
+        for (position, allocation) in allocations.iter().enumerate() {
+            let other_order = orderbook
+                .get(allocation.index)
+                .ok_or(MatchError::UnknownOrder(allocation.index))?;
+            if other_order.user == order.user {
+                self_trade = Some(position);
+                break;
+            }
+        }
+        let Some(position) = self_trade else {
+            break allocations;
+        };
+
+        let index = allocations[position].index;
+        let mut new_other_order = orderbook[index].clone();
+        let mode = config.self_trade_prevention;
+        result.self_trade = Some(mode);
+
+        match mode {
+            SelfTradePrevention::CancelNewest => {
+                result.new_order.isCancelled = true;
+            }
+            SelfTradePrevention::CancelOldest => {
+                new_other_order.isCancelled = true;
+            }
+            SelfTradePrevention::CancelBoth => {
+                new_other_order.isCancelled = true;
+                result.new_order.isCancelled = true;
+            }
+            SelfTradePrevention::DecrementAndCancel => {
+                // Only what is left after the fills ahead of the self-trade can be decremented
+                let ahead = allocations[..position]
+                    .iter()
+                    .try_fold(U256::ZERO, |sum, allocation| {
+                        add(sum, allocation.amount, "fills ahead of a self-trade")
+                    })?;
+                let decrement = sub(remaining, ahead, "self-trade decrement")?
+                    .min(new_other_order.amount_owned);
+
+                // Both orders shrink at their own price, nothing is exchanged
+                let given = price_for_user
+                    .mul_ceil(decrement)
+                    .ok_or(MatchError::Overflow("self-trade decrement"))?
+                    .min(budget);
+                remaining = sub(remaining, decrement, "self-trade decrement")?;
+                budget = sub(budget, given, "self-trade decrement")?;
+                let decrement_legs = &mut result.self_trade_decrement;
+                decrement_legs.received =
+                    add(decrement_legs.received, decrement, "self-trade decrement")?;
+                decrement_legs.given = add(decrement_legs.given, given, "self-trade decrement")?;
+
+                let other_received = Price::of(&new_other_order)
+                    .ok_or(MatchError::ZeroAmount)?
+                    .div_floor(decrement)
+                    .ok_or(MatchError::Overflow("self-trade decrement"))?
+                    .min(new_other_order.amount_not_owned);
+                new_other_order.amount_owned = sub(
+                    new_other_order.amount_owned,
+                    decrement,
+                    "self-trade decrement",
+                )?;
+                new_other_order.amount_not_owned = sub(
+                    new_other_order.amount_not_owned,
+                    other_received,
+                    "self-trade decrement",
+                )?;
+                if new_other_order.amount_owned.is_zero() {
+                    new_other_order.isCancelled = true;
+                }
+                consume(&mut new_other_order, decrement, order.timestamp);
+                if remaining == ahead {
+                    result.new_order.isCancelled = true;
+                }
+            }
+        }
+
+        if mode != SelfTradePrevention::CancelNewest {
+            result.self_trade_updates.push(OrderUpdate {
+                index,
+                new_order: new_other_order,
+            });
+        }
+
+        if result.new_order.isCancelled {
+            allocations.truncate(position);
+            break allocations;
+        }
+        candidates.retain(|candidate| candidate.index != index);
+    };
+
+    let mut fills = Vec::new();
+    let (mut left, mut budget_left) = (remaining, budget);
+    for allocation in allocations {
+        let other_order = orderbook
+            .get(allocation.index)
+            .ok_or(MatchError::UnknownOrder(allocation.index))?;
+
+        // The incoming order receives `amount` and pays for it at the execution price,
+        // rounded in favour of the resting order
+        let mut amount = allocation.amount.min(left);
+        let mut paid = allocation
+            .price
+            .mul_ceil(amount)
+            .ok_or(MatchError::Overflow("fill cost"))?;
+        if paid > budget_left {
+            amount = allocation
+                .price
+                .div_floor(budget_left)
+                .ok_or(MatchError::Overflow("affordable fill"))?;
+            paid = allocation
+                .price
+                .mul_ceil(amount)
+                .ok_or(MatchError::Overflow("fill cost"))?;
+        }
+        // The resting order never receives more than it asked for
+        paid = paid.min(other_order.amount_not_owned);
+        if amount.is_zero() {
+            continue;
+        }
+        left = sub(left, amount, "incoming order remainder")?;
+        budget_left = sub(budget_left, paid, "incoming order budget")?;
+
+        let mut new_other_order = other_order.clone();
+        new_other_order.amount_owned =
+            sub(other_order.amount_owned, amount, "resting order remainder")?;
+        new_other_order.amount_not_owned = sub(
+            other_order.amount_not_owned,
+            paid,
+            "resting order remainder",
+        )?;
+        new_other_order.timestamp_matched = order.timestamp;
+        if new_other_order.amount_owned.is_zero() || new_other_order.amount_not_owned.is_zero() {
+            new_other_order.isFilled = true;
+        } else {
+            new_other_order.isPartiallyFilled = true;
+        }
+        consume(&mut new_other_order, amount, order.timestamp);
+
+        fills.push(MatchFill {
+            index: allocation.index,
+            new_order: new_other_order,
+            amount,
+            paid,
+            maker_fee: config.fees.maker_fee(paid),
+            taker_fee: config.fees.taker_fee(amount),
+            price: allocation.price,
+        });
+    }
+
+    // Without a direct match the order may still be filled through a ring
+    let mut rings = Vec::new();
+    if fills.is_empty() && result.self_trade.is_none() && order_type != OrderType::PostOnly {
+        rings = match_rings(
+            order,
+            orderbook,
+            RingBudget {
+                remaining: left,
+                budget: budget_left,
+                max_fills: config.max_fills,
+            },
+            config.max_ring_length,
+            &config.batch_markets,
+        )?;
+        let (given, received) = ring_totals(&rings)?;
+        left = sub(left, received, "incoming order remainder")?;
+        budget_left = sub(budget_left, given, "incoming order budget")?;
+    }
+
+    if order_type == OrderType::FillOrKill && !left.is_zero() {
+        result.new_order.amount_not_owned = remaining;
+        result.new_order.amount_owned = budget;
+        result.new_order.isCancelled = true;
+        return Ok(result);
+    }
+
+    result.fills = fills;
+    result.rings = rings;
+    result.new_order.amount_not_owned = left;
+    result.new_order.amount_owned = budget_left;
+    let filled = left.is_zero() || budget_left.is_zero();
+    if result.is_match() {
+        result.new_order.timestamp_matched = order.timestamp;
+        if filled {
+            result.new_order.isFilled = true;
+        } else {
+            result.new_order.isPartiallyFilled = true;
+        }
+    }
+
+    if !filled && !order_type.can_rest() {
+        result.new_order.isCancelled = true;
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::market::Market;
+    use crate::matching::spec::MarketSpec;
+    use crate::matching::strategy::{Allocation, ProRata};
+    use crate::matching::test_support::{config, order, DAI, USDC, WETH};
+    use crate::matching::trigger::TriggerType;
+
+    fn with_stp(mode: SelfTradePrevention) -> MatchingConfig {
+        MatchingConfig {
+            self_trade_prevention: mode,
+            ..config()
+        }
+    }
+
+    fn with_type(mut order: Order, order_type: OrderType) -> Order {
+        order.orderType = order_type.into();
+        order
+    }
+
+    fn matched_indices(result: &MatchResult) -> Vec<usize> {
+        result.fills.iter().map(|fill| fill.index).collect()
+    }
+
+    #[test]
+    fn test_full_match() {
+        let incoming = order(1, WETH, 5, USDC, 5, 5);
+        let book = vec![order(2, USDC, 5, WETH, 5, 5)];
+
+        let result = match_order(&incoming, &book, &config()).unwrap();
+        assert_eq!(matched_indices(&result), vec![0]);
+        assert!(result.new_order.isFilled);
+        assert_eq!(result.new_order.amount_not_owned, U256::ZERO);
+        let fill = &result.fills[0].new_order;
+        assert!(fill.isFilled);
+        assert_eq!(fill.amount_not_owned, U256::ZERO);
+        assert_eq!(fill.user, book[0].user);
+    }
+
+    #[test]
+    fn test_partial_match_resting_order_larger() {
+        let incoming = order(1, WETH, 5, USDC, 5, 5);
+        let book = vec![order(2, USDC, 10, WETH, 10, 5)];
+
+        let result = match_order(&incoming, &book, &config()).unwrap();
+        assert_eq!(matched_indices(&result), vec![0]);
+        assert!(result.new_order.isFilled);
+        let fill = &result.fills[0].new_order;
+        assert!(fill.isPartiallyFilled);
+        assert!(!fill.isFilled);
+        assert_eq!(fill.amount_not_owned, U256::from(5));
+    }
+
+    #[test]
+    fn test_partial_match_incoming_order_larger() {
+        let incoming = order(1, WETH, 10, USDC, 10, 5);
+        let book = vec![order(2, USDC, 5, WETH, 5, 5)];
+
+        let result = match_order(&incoming, &book, &config()).unwrap();
+        assert_eq!(matched_indices(&result), vec![0]);
+        assert!(result.new_order.isPartiallyFilled);
+        assert!(!result.new_order.isFilled);
+        assert_eq!(result.new_order.amount_not_owned, U256::from(5));
+        assert!(result.fills[0].new_order.isFilled);
+    }
+
+    #[test]
+    fn test_sweeps_multiple_resting_orders() {
+        let incoming = order(1, WETH, 12, USDC, 12, 5);
+        let book = vec![
+            order(2, USDC, 5, WETH, 5, 5),
+            order(3, USDC, 4, WETH, 4, 5),
+            order(4, USDC, 6, WETH, 6, 5),
+        ];
+
+        let result = match_order(&incoming, &book, &config()).unwrap();
+        assert_eq!(matched_indices(&result), vec![0, 1, 2]);
+        assert!(result.new_order.isFilled);
+        assert_eq!(result.new_order.amount_not_owned, U256::ZERO);
+        assert!(result.fills[0].new_order.isFilled);
+        assert!(result.fills[1].new_order.isFilled);
+        assert!(result.fills[2].new_order.isPartiallyFilled);
+        assert_eq!(result.fills[2].new_order.amount_not_owned, U256::from(3));
+    }
+
+    #[test]
+    fn test_sweep_stops_once_filled() {
+        let incoming = order(1, WETH, 5, USDC, 5, 5);
+        let book = vec![order(2, USDC, 5, WETH, 5, 5), order(3, USDC, 5, WETH, 5, 5)];
+
+        let result = match_order(&incoming, &book, &config()).unwrap();
+        assert_eq!(matched_indices(&result), vec![0]);
+    }
+
+    #[test]
+    fn test_sweep_respects_max_fills() {
+        let incoming = order(1, WETH, 10, USDC, 10, 5);
+        let book = vec![order(2, USDC, 5, WETH, 5, 5), order(3, USDC, 5, WETH, 5, 5)];
+
+        let result = match_order(
+            &incoming,
+            &book,
+            &MatchingConfig {
+                max_fills: 1,
+                ..config()
+            },
+        )
+        .unwrap();
+        assert_eq!(matched_indices(&result), vec![0]);
+        assert!(result.new_order.isPartiallyFilled);
+        assert_eq!(result.new_order.amount_not_owned, U256::from(5));
+    }
+
+    #[test]
+    fn test_best_price_first() {
+        let incoming = order(1, WETH, 20, USDC, 20, 5);
+        // Asking 1, 0.5 and 0.8 WETH per USDC respectively
+        let book = vec![
+            order(2, USDC, 5, WETH, 5, 5),
+            order(3, USDC, 10, WETH, 5, 5),
+            order(4, USDC, 5, WETH, 4, 5),
+        ];
+
+        let mut incoming_with_slippage = incoming.clone();
+        incoming_with_slippage.slippage = U256::from(100);
+        let result = match_order(&incoming_with_slippage, &book, &config()).unwrap();
+        assert_eq!(matched_indices(&result), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn test_time_priority_breaks_price_ties() {
+        let incoming = order(1, WETH, 5, USDC, 5, 5);
+        let mut older = order(2, USDC, 5, WETH, 5, 5);
+        older.timestamp = U256::from(100);
+        let mut newer = order(3, USDC, 5, WETH, 5, 5);
+        newer.timestamp = U256::from(200);
+
+        let result = match_order(&incoming, &[newer, older], &config()).unwrap();
+        assert_eq!(matched_indices(&result), vec![1]);
+    }
+
+    #[test]
+    fn test_index_breaks_price_and_time_ties() {
+        let incoming = order(1, WETH, 5, USDC, 5, 5);
+        let book = vec![order(2, USDC, 5, WETH, 5, 5), order(3, USDC, 5, WETH, 5, 5)];
+
+        let result = match_order(&incoming, &book, &config()).unwrap();
+        assert_eq!(matched_indices(&result), vec![0]);
+    }
+
+    #[test]
+    fn test_equivalent_prices_are_tied() {
+        let incoming = order(1, WETH, 5, USDC, 5, 5);
+        let mut larger = order(2, USDC, 10, WETH, 10, 5);
+        larger.timestamp = U256::from(200);
+        let mut smaller = order(3, USDC, 5, WETH, 5, 5);
+        smaller.timestamp = U256::from(100);
+
+        let result = match_order(&incoming, &[larger, smaller], &config()).unwrap();
+        assert_eq!(matched_indices(&result), vec![1]);
+    }
+
+    #[test]
+    fn test_strategy_decides_allocation() {
+        let incoming = order(1, WETH, 4, USDC, 4, 5);
+        let book = vec![order(2, USDC, 6, WETH, 6, 5), order(3, USDC, 2, WETH, 2, 5)];
+
+        let result = match_order(
+            &incoming,
+            &book,
+            &MatchingConfig {
+                strategy: Arc::new(ProRata),
+                ..config()
+            },
+        )
+        .unwrap();
+        assert_eq!(matched_indices(&result), vec![0, 1]);
+        assert_eq!(result.fills[0].amount, U256::from(3));
+        assert_eq!(result.fills[1].amount, U256::from(1));
+        assert!(result.new_order.isFilled);
+        assert_eq!(result.fills[0].new_order.amount_not_owned, U256::from(3));
+    }
+
+    #[test]
+    fn test_fill_updates_both_legs() {
+        // 10 WETH for 20 USDC against 30 USDC for 12 WETH: 20 USDC trade for 8 WETH
+        let mut incoming = order(1, WETH, 10, USDC, 20, 5);
+        incoming.slippage = U256::from(30);
+        incoming.timestamp = U256::from(1_000);
+        let mut resting = order(2, USDC, 30, WETH, 12, 5);
+        resting.timestamp = U256::from(500);
+
+        let result = match_order(&incoming, &[resting.clone()], &config()).unwrap();
+        let fill = &result.fills[0];
+        assert_eq!(fill.amount, U256::from(20));
+        assert_eq!(fill.paid, U256::from(8));
+
+        assert_eq!(fill.new_order.amount_owned, U256::from(10));
+        assert_eq!(fill.new_order.amount_not_owned, U256::from(4));
+        assert_eq!(fill.new_order.timestamp_matched, U256::from(1_000));
+        assert!(fill.new_order.isPartiallyFilled);
+
+        assert_eq!(result.new_order.amount_owned, U256::from(2));
+        assert_eq!(result.new_order.amount_not_owned, U256::ZERO);
+        assert_eq!(result.new_order.timestamp_matched, U256::from(1_000));
+        assert!(result.new_order.isFilled);
+
+        assert!(result.is_balanced(&incoming, &[resting]));
+    }
+
+    #[test]
+    fn test_fill_rounds_in_favour_of_resting_order() {
+        // 3 USDC at 2/3 WETH per USDC cost 2 WETH, 1 USDC costs 2/3 rounded up to 1
+        let incoming = order(1, WETH, 1, USDC, 1, 5);
+        let resting = order(2, USDC, 3, WETH, 2, 5);
+
+        let mut incoming_with_slippage = incoming.clone();
+        incoming_with_slippage.slippage = U256::from(50);
+        let result = match_order(&incoming_with_slippage, &[resting.clone()], &config()).unwrap();
+        assert_eq!(result.fills[0].amount, U256::from(1));
+        assert_eq!(result.fills[0].paid, U256::from(1));
+        assert!(result.is_balanced(&incoming_with_slippage, &[resting]));
+    }
+
+    #[test]
+    fn test_fill_limited_by_what_the_incoming_order_can_pay() {
+        // At 6/5 WETH per USDC, 5 WETH only buy 4 USDC
+        let mut incoming = with_type(order(1, WETH, 5, USDC, 5, 5), OrderType::Market);
+        incoming.slippage = U256::from(20);
+        let resting = order(2, USDC, 5, WETH, 6, 5);
+
+        let result = match_order(&incoming, &[resting.clone()], &config()).unwrap();
+        assert_eq!(result.fills[0].amount, U256::from(4));
+        assert_eq!(result.fills[0].paid, U256::from(5));
+        assert_eq!(result.new_order.amount_owned, U256::ZERO);
+        assert!(result.new_order.isFilled);
+        assert!(result.is_balanced(&incoming, &[resting]));
+    }
+
+    #[test]
+    fn test_sweep_is_balanced() {
+        let incoming = order(1, WETH, 20, USDC, 20, 5);
+        let book = vec![
+            order(2, USDC, 5, WETH, 5, 5),
+            order(3, USDC, 10, WETH, 5, 5),
+            order(4, USDC, 5, WETH, 4, 5),
+        ];
+
+        let mut incoming_with_slippage = incoming.clone();
+        incoming_with_slippage.slippage = U256::from(100);
+        let result = match_order(&incoming_with_slippage, &book, &config()).unwrap();
+        assert_eq!(result.new_order.amount_owned, U256::from(6));
+        assert!(result.is_balanced(&incoming_with_slippage, &book));
+    }
+
+    #[test]
+    fn test_fees_charged_on_what_each_side_receives() {
+        let mut incoming = order(1, WETH, 10, USDC, 20, 5);
+        incoming.slippage = U256::from(30);
+        let book = vec![
+            order(2, USDC, 10, WETH, 4, 5),
+            order(3, USDC, 30, WETH, 12, 5),
+        ];
+
+        let result = match_order(
+            &incoming,
+            &book,
+            &MatchingConfig {
+                fees: FeeSchedule::new(2_500, 2_000).unwrap(),
+                ..config()
+            },
+        )
+        .unwrap();
+        assert_eq!(matched_indices(&result), vec![0, 1]);
+        assert_eq!(result.fills[0].amount, U256::from(10));
+        assert_eq!(result.fills[0].paid, U256::from(4));
+        assert_eq!(result.fills[0].maker_fee, U256::from(1));
+        assert_eq!(result.fills[0].taker_fee, U256::from(2));
+        assert_eq!(result.fills[1].amount, U256::from(10));
+        assert_eq!(result.fills[1].paid, U256::from(4));
+        assert_eq!(result.fills[1].maker_fee, U256::from(1));
+        assert_eq!(result.fills[1].taker_fee, U256::from(2));
+        assert_eq!(result.taker_fee(), U256::from(4));
+
+        // Fees are reported next to the fills and do not change the traded amounts
+        assert!(result.is_balanced(&incoming, &book));
+    }
+
+    #[test]
+    fn test_unbalanced_result_is_detected() {
+        let mut incoming = order(1, WETH, 10, USDC, 20, 5);
+        incoming.slippage = U256::from(30);
+        let book = vec![order(2, USDC, 30, WETH, 12, 5)];
+
+        let mut result = match_order(&incoming, &book, &config()).unwrap();
+        result.fills[0].new_order.amount_not_owned = U256::from(5);
+        assert!(!result.is_balanced(&incoming, &book));
+
+        let mut result = match_order(&incoming, &book, &config()).unwrap();
+        result.new_order.amount_owned = U256::from(3);
+        assert!(!result.is_balanced(&incoming, &book));
+    }
+
+    #[test]
+    fn test_no_match_on_empty_book() {
+        let incoming = order(1, WETH, 5, USDC, 5, 5);
+
+        let result = match_order(&incoming, &[], &config()).unwrap();
+        assert!(!result.is_match());
+        assert_eq!(result.outcome(), MatchOutcome::Resting);
+        assert!(result.fills.is_empty());
+        assert!(!result.new_order.isFilled);
+        assert!(!result.new_order.isPartiallyFilled);
+        assert_eq!(result.new_order.amount_owned, incoming.amount_owned);
+        assert_eq!(result.new_order.amount_not_owned, incoming.amount_not_owned);
+    }
+
+    #[test]
+    fn test_match_outcome() {
+        let incoming = order(1, WETH, 5, USDC, 5, 5);
+        let result = match_order(&incoming, &[order(2, USDC, 5, WETH, 5, 5)], &config()).unwrap();
+        assert_eq!(result.outcome(), MatchOutcome::Matched);
+
+        // Resting orders that do not cross are left alone
+        let book = vec![order(2, USDC, 5, WETH, 6, 5)];
+        let result = match_order(&incoming, &book, &config()).unwrap();
+        assert_eq!(result.outcome(), MatchOutcome::Resting);
+        assert!(result.is_balanced(&incoming, &book));
+
+        let incoming = with_type(incoming, OrderType::ImmediateOrCancel);
+        let result = match_order(&incoming, &book, &config()).unwrap();
+        assert_eq!(result.outcome(), MatchOutcome::Cancelled);
+
+        let result = match_order(&incoming, &[order(2, USDC, 3, WETH, 3, 5)], &config()).unwrap();
+        assert_eq!(result.outcome(), MatchOutcome::Matched);
+        assert!(result.new_order.isCancelled);
+    }
+
+    #[test]
+    fn test_no_match_same_user() {
+        let incoming = order(1, WETH, 5, USDC, 5, 5);
+        let book = vec![order(1, USDC, 5, WETH, 5, 5)];
+
+        let result = match_order(&incoming, &book, &config()).unwrap();
+        assert!(!result.is_match());
+        assert_eq!(result.self_trade, Some(SelfTradePrevention::CancelNewest));
+    }
+
+    #[test]
+    fn test_no_match_same_side() {
+        let incoming = order(1, WETH, 5, USDC, 5, 5);
+        let book = vec![order(2, WETH, 5, USDC, 5, 5)];
+
+        assert!(!match_order(&incoming, &book, &config()).unwrap().is_match());
+    }
+
+    #[test]
+    fn test_no_match_different_pair() {
+        let incoming = order(1, WETH, 5, USDC, 5, 5);
+        let mut resting = order(2, USDC, 5, WETH, 5, 5);
+        resting.token_owned = DAI;
+
+        assert!(!match_order(&incoming, &[resting.clone()], &config())
+            .unwrap()
+            .is_match());
+
+        resting.token_owned = USDC;
+        resting.token_not_owned = DAI;
+        assert!(!match_order(&incoming, &[resting], &config())
+            .unwrap()
+            .is_match());
+    }
+
+    #[test]
+    fn test_ring_match_without_direct_counterparty() {
+        // WETH -> USDC, USDC -> DAI and DAI -> WETH
+        let incoming = order(1, WETH, 10, USDC, 100, 5);
+        let mut usdc_for_dai = order(2, USDC, 100, WETH, 100, 5);
+        usdc_for_dai.token_not_owned = DAI;
+        let book = vec![usdc_for_dai, order(3, DAI, 100, WETH, 10, 5)];
+
+        let result = match_order(&incoming, &book, &config()).unwrap();
+        assert!(result.fills.is_empty());
+        assert_eq!(result.rings.len(), 1);
+        assert_eq!(result.outcome(), MatchOutcome::Matched);
+        assert!(result.new_order.isFilled);
+        assert!(result.is_balanced(&incoming, &book));
+
+        let disabled = MatchingConfig {
+            max_ring_length: 2,
+            ..config()
+        };
+        assert!(!match_order(&incoming, &book, &disabled).unwrap().is_match());
+    }
+
+    #[test]
+    fn test_no_match_filled_order() {
+        let incoming = order(1, WETH, 5, USDC, 5, 5);
+        let mut resting = order(2, USDC, 5, WETH, 5, 5);
+        resting.isFilled = true;
+
+        assert!(!match_order(&incoming, &[resting], &config())
+            .unwrap()
+            .is_match());
+    }
+
+    #[test]
+    fn test_skips_unacceptable_orders() {
+        let incoming = order(1, WETH, 5, USDC, 5, 5);
+        let mut filled = order(4, USDC, 5, WETH, 5, 5);
+        filled.isFilled = true;
+        let book = vec![
+            filled,
+            order(3, WETH, 5, USDC, 5, 5),
+            order(2, USDC, 5, WETH, 5, 5),
+        ];
+
+        assert_eq!(
+            matched_indices(&match_order(&incoming, &book, &config()).unwrap()),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn test_match_prices_below_one() {
+        // 5 WETH for 200 USDC against 210 USDC for 5 WETH: both prices truncate to zero
+        let incoming = order(1, WETH, 5, USDC, 200, 5);
+        let book = vec![order(2, USDC, 210, WETH, 5, 5)];
+
+        assert!(match_order(&incoming, &book, &config()).unwrap().is_match());
+    }
+
+    #[test]
+    fn test_no_match_when_resting_price_is_worse() {
+        // The resting order asks 6/190 WETH per USDC, more than the 5/200 offered
+        let incoming = order(1, WETH, 5, USDC, 200, 5);
+        let book = vec![order(2, USDC, 190, WETH, 6, 5)];
+
+        assert!(!match_order(&incoming, &book, &config()).unwrap().is_match());
+    }
+
+    #[test]
+    fn test_no_match_outside_slippage() {
+        let incoming = order(1, WETH, 5, USDC, 200, 5);
+        let book = vec![order(2, USDC, 400, WETH, 5, 5)];
+
+        assert!(!match_order(&incoming, &book, &config()).unwrap().is_match());
+    }
+
+    #[test]
+    fn test_zero_amount_orders_are_rejected() {
+        let book = vec![order(2, USDC, 5, WETH, 5, 5)];
+        assert_eq!(
+            match_order(&order(1, WETH, 5, USDC, 0, 5), &book, &config()).unwrap_err(),
+            MatchError::ZeroAmount
+        );
+        assert_eq!(
+            match_order(&order(1, WETH, 0, USDC, 5, 5), &book, &config()).unwrap_err(),
+            MatchError::ZeroAmount
+        );
+
+        // Resting orders with a zero amount are skipped rather than failing the task
+        let incoming = order(1, WETH, 5, USDC, 5, 5);
+        assert!(
+            !match_order(&incoming, &[order(2, USDC, 0, WETH, 5, 5)], &config())
+                .unwrap()
+                .is_match()
+        );
+    }
+
+    #[test]
+    fn test_orders_violating_the_market_spec_are_rejected() {
+        let mut markets = MarketSpecs::default();
+        markets.insert(
+            Market::new(WETH, USDC).unwrap(),
+            MarketSpec {
+                lot_size: U256::from(5),
+                ..MarketSpec::default()
+            },
+        );
+        let config = MatchingConfig {
+            markets: Arc::new(markets),
+            ..config()
+        };
+        let book = vec![order(2, USDC, 5, WETH, 5, 5)];
+
+        let result = match_order(&order(1, WETH, 7, USDC, 7, 5), &book, &config).unwrap();
+        assert!(!result.is_match());
+        assert_eq!(result.outcome(), MatchOutcome::Rejected);
+        assert_eq!(result.rejection, Some(Rejection::InvalidLotSize));
+        assert!(result.new_order.isCancelled);
+
+        let result = match_order(&order(1, WETH, 5, USDC, 5, 5), &book, &config).unwrap();
+        assert_eq!(result.outcome(), MatchOutcome::Matched);
+        assert_eq!(result.rejection, None);
+    }
+
+    #[test]
+    fn test_same_token_on_both_sides_is_rejected() {
+        let mut incoming = order(1, WETH, 5, USDC, 5, 5);
+        incoming.token_not_owned = WETH;
+        assert_eq!(
+            match_order(&incoming, &[order(2, USDC, 5, WETH, 5, 5)], &config()).unwrap_err(),
+            MatchError::InvalidPair
+        );
+    }
+
+    #[test]
+    fn test_allocation_to_unknown_order_is_an_error() {
+        #[derive(Debug)]
+        struct OutOfBounds;
+
+        impl MatchingStrategy for OutOfBounds {
+            fn name(&self) -> &'static str {
+                "out-of-bounds"
+            }
+
+            fn allocate(
+                &self,
+                limit: Price,
+                amount: U256,
+                _candidates: &[Candidate],
+                _max_fills: usize,
+            ) -> Result<Vec<Allocation>, MatchError> {
+                Ok(vec![Allocation {
+                    index: 7,
+                    amount,
+                    price: limit,
+                }])
+            }
+        }
+
+        let result = match_order(
+            &order(1, WETH, 5, USDC, 5, 5),
+            &[order(2, USDC, 5, WETH, 5, 5)],
+            &MatchingConfig {
+                strategy: Arc::new(OutOfBounds),
+                ..config()
+            },
+        );
+        assert_eq!(result.unwrap_err(), MatchError::UnknownOrder(7));
+    }
+
+    #[test]
+    fn test_large_amounts_do_not_panic() {
+        let incoming = order(1, WETH, 5, USDC, 5, 5);
+        let mut incoming_max = incoming.clone();
+        incoming_max.amount_owned = U256::MAX;
+        incoming_max.amount_not_owned = U256::MAX;
+        let mut resting = order(2, USDC, 5, WETH, 5, 5);
+        resting.amount_owned = U256::MAX;
+        resting.amount_not_owned = U256::MAX;
+
+        let result = match_order(&incoming_max, &[resting.clone()], &config()).unwrap();
+        assert!(result.new_order.isFilled);
+        assert!(result.is_balanced(&incoming_max, &[resting.clone()]));
+
+        let result = match_order(&incoming, &[resting.clone()], &config()).unwrap();
+        assert!(result.new_order.isFilled);
+        assert!(result.is_balanced(&incoming, &[resting]));
+    }
+
+    #[test]
+    fn test_limit_order_rests_remainder() {
+        let incoming = order(1, WETH, 10, USDC, 10, 5);
+        let result = match_order(&incoming, &[order(2, USDC, 5, WETH, 5, 5)], &config()).unwrap();
+        assert!(result.new_order.isPartiallyFilled);
+        assert!(!result.new_order.isCancelled);
+
+        let result = match_order(&incoming, &[], &config()).unwrap();
+        assert!(!result.new_order.isCancelled);
+    }
+
+    #[test]
+    fn test_market_order_ignores_price_within_slippage() {
+        // The resting order asks 6/5 WETH per USDC, above the 1:1 quoted price
+        let incoming = with_type(order(1, WETH, 5, USDC, 5, 5), OrderType::Market);
+        let resting = order(2, USDC, 5, WETH, 6, 5);
+
+        let mut incoming_with_slippage = incoming.clone();
+        incoming_with_slippage.slippage = U256::from(20);
+        let result = match_order(&incoming_with_slippage, &[resting.clone()], &config()).unwrap();
+        assert!(result.is_match());
+
+        assert!(!match_order(&incoming, &[resting.clone()], &config())
+            .unwrap()
+            .is_match());
+
+        let mut limit = order(1, WETH, 5, USDC, 5, 5);
+        limit.slippage = U256::from(20);
+        assert!(!match_order(&limit, &[resting], &config())
+            .unwrap()
+            .is_match());
+    }
+
+    #[test]
+    fn test_market_order_never_rests() {
+        let incoming = with_type(order(1, WETH, 10, USDC, 10, 5), OrderType::Market);
+        let result = match_order(&incoming, &[order(2, USDC, 5, WETH, 5, 5)], &config()).unwrap();
+        assert!(result.is_match());
+        assert!(result.new_order.isCancelled);
+    }
+
+    #[test]
+    fn test_immediate_or_cancel_cancels_remainder() {
+        let incoming = with_type(
+            order(1, WETH, 10, USDC, 10, 5),
+            OrderType::ImmediateOrCancel,
+        );
+        let result = match_order(&incoming, &[order(2, USDC, 5, WETH, 5, 5)], &config()).unwrap();
+        assert_eq!(matched_indices(&result), vec![0]);
+        assert_eq!(result.new_order.amount_not_owned, U256::from(5));
+        assert!(result.new_order.isCancelled);
+
+        let result = match_order(&incoming, &[order(2, USDC, 10, WETH, 10, 5)], &config()).unwrap();
+        assert!(result.new_order.isFilled);
+        assert!(!result.new_order.isCancelled);
+
+        let result = match_order(&incoming, &[], &config()).unwrap();
+        assert!(result.new_order.isCancelled);
+    }
+
+    #[test]
+    fn test_fill_or_kill_requires_complete_fill() {
+        let incoming = with_type(order(1, WETH, 10, USDC, 10, 5), OrderType::FillOrKill);
+        let result = match_order(&incoming, &[order(2, USDC, 5, WETH, 5, 5)], &config()).unwrap();
+        assert!(!result.is_match());
+        assert!(result.new_order.isCancelled);
+
+        let book = vec![order(2, USDC, 5, WETH, 5, 5), order(3, USDC, 5, WETH, 5, 5)];
+        let result = match_order(&incoming, &book, &config()).unwrap();
+        assert_eq!(matched_indices(&result), vec![0, 1]);
+        assert!(result.new_order.isFilled);
+        assert!(!result.new_order.isCancelled);
+
+        let result = match_order(
+            &incoming,
+            &book,
+            &MatchingConfig {
+                max_fills: 1,
+                ..config()
+            },
+        )
+        .unwrap();
+        assert!(!result.is_match());
+        assert!(result.new_order.isCancelled);
+    }
+
+    #[test]
+    fn test_post_only_rejected_when_crossing() {
+        let incoming = with_type(order(1, WETH, 5, USDC, 5, 5), OrderType::PostOnly);
+        let result = match_order(&incoming, &[order(2, USDC, 5, WETH, 5, 5)], &config()).unwrap();
+        assert!(!result.is_match());
+        assert!(result.new_order.isCancelled);
+
+        let result = match_order(&incoming, &[order(2, USDC, 5, WETH, 6, 5)], &config()).unwrap();
+        assert!(!result.is_match());
+        assert!(!result.new_order.isCancelled);
+    }
+
+    #[test]
+    fn test_post_only_crossing_only_own_orders_applies_self_trade_prevention() {
+        let incoming = with_type(order(1, WETH, 5, USDC, 5, 5), OrderType::PostOnly);
+        let book = vec![order(1, USDC, 5, WETH, 5, 5)];
+
+        let result = match_order(
+            &incoming,
+            &book,
+            &with_stp(SelfTradePrevention::CancelOldest),
+        )
+        .unwrap();
+        assert!(!result.is_match());
+        assert!(!result.new_order.isCancelled);
+        assert_eq!(result.self_trade, Some(SelfTradePrevention::CancelOldest));
+        assert_eq!(result.self_trade_updates.len(), 1);
+        assert!(result.self_trade_updates[0].new_order.isCancelled);
+
+        // Another user's crossing order still cancels it
+        let book = vec![order(1, USDC, 5, WETH, 5, 5), order(2, USDC, 5, WETH, 5, 5)];
+        let result = match_order(
+            &incoming,
+            &book,
+            &with_stp(SelfTradePrevention::CancelOldest),
+        )
+        .unwrap();
+        assert!(result.new_order.isCancelled);
+        assert!(result.self_trade_updates.is_empty());
+    }
+
+    #[test]
+    fn test_non_resting_orders_are_not_matched_against() {
+        let incoming = order(1, WETH, 5, USDC, 5, 5);
+        for order_type in [
+            OrderType::Market,
+            OrderType::ImmediateOrCancel,
+            OrderType::FillOrKill,
+        ] {
+            let resting = with_type(order(2, USDC, 5, WETH, 5, 5), order_type);
+            assert!(!match_order(&incoming, &[resting], &config())
+                .unwrap()
+                .is_match());
+        }
+
+        let resting = with_type(order(2, USDC, 5, WETH, 5, 5), OrderType::PostOnly);
+        assert!(match_order(&incoming, &[resting], &config())
+            .unwrap()
+            .is_match());
+
+        let mut cancelled = order(2, USDC, 5, WETH, 5, 5);
+        cancelled.isCancelled = true;
+        assert!(!match_order(&incoming, &[cancelled], &config())
+            .unwrap()
+            .is_match());
+    }
+
+    #[test]
+    fn test_unknown_order_type_is_cancelled() {
+        let mut incoming = order(1, WETH, 5, USDC, 5, 5);
+        incoming.orderType = 9;
+        let result = match_order(&incoming, &[order(2, USDC, 5, WETH, 5, 5)], &config()).unwrap();
+        assert!(!result.is_match());
+        assert!(result.new_order.isCancelled);
+    }
+
+    #[test]
+    fn test_expired_orders_are_skipped_and_reported() {
+        let mut incoming = order(1, WETH, 5, USDC, 5, 5);
+        incoming.timestamp = U256::from(1_000);
+        let mut expired = order(2, USDC, 5, WETH, 5, 5);
+        expired.expiry = U256::from(1_000);
+        let mut live = order(3, USDC, 5, WETH, 5, 5);
+        live.expiry = U256::from(1_001);
+        let good_til_cancelled = order(4, USDC, 5, WETH, 5, 5);
+
+        let result =
+            match_order(&incoming, &[expired, live, good_til_cancelled], &config()).unwrap();
+        assert_eq!(matched_indices(&result), vec![1]);
+        assert_eq!(result.expired, vec![0]);
+    }
+
+    #[test]
+    fn test_stp_cancel_newest() {
+        let incoming = order(1, WETH, 10, USDC, 10, 5);
+        let mut own = order(1, USDC, 5, WETH, 5, 5);
+        own.timestamp = U256::from(2);
+        let mut better = order(2, USDC, 5, WETH, 4, 5);
+        better.timestamp = U256::from(3);
+        let mut later = order(3, USDC, 5, WETH, 5, 5);
+        later.timestamp = U256::from(3);
+
+        let mut incoming_with_slippage = incoming.clone();
+        incoming_with_slippage.slippage = U256::from(50);
+        let book = vec![own, better, later];
+        let result = match_order(&incoming_with_slippage, &book, &config()).unwrap();
+
+        // Fills ahead of the self-trade stand, the rest of the incoming order is cancelled
+        assert_eq!(matched_indices(&result), vec![1]);
+        assert!(result.new_order.isCancelled);
+        assert!(result.new_order.isPartiallyFilled);
+        assert_eq!(result.self_trade, Some(SelfTradePrevention::CancelNewest));
+        assert!(result.self_trade_updates.is_empty());
+    }
+
+    #[test]
+    fn test_stp_cancel_oldest() {
+        let incoming = order(1, WETH, 5, USDC, 5, 5);
+        let book = vec![order(1, USDC, 5, WETH, 5, 5), order(2, USDC, 5, WETH, 5, 5)];
+
+        let result = match_order(
+            &incoming,
+            &book,
+            &with_stp(SelfTradePrevention::CancelOldest),
+        )
+        .unwrap();
+        assert_eq!(matched_indices(&result), vec![1]);
+        assert!(result.new_order.isFilled);
+        assert!(!result.new_order.isCancelled);
+        assert_eq!(result.self_trade_updates.len(), 1);
+        assert_eq!(result.self_trade_updates[0].index, 0);
+        assert!(result.self_trade_updates[0].new_order.isCancelled);
+    }
+
+    #[test]
+    fn test_stp_cancel_both() {
+        let incoming = order(1, WETH, 5, USDC, 5, 5);
+        let book = vec![order(1, USDC, 5, WETH, 5, 5), order(2, USDC, 5, WETH, 5, 5)];
+
+        let result =
+            match_order(&incoming, &book, &with_stp(SelfTradePrevention::CancelBoth)).unwrap();
+        assert!(!result.is_match());
+        assert!(result.new_order.isCancelled);
+        assert_eq!(result.self_trade_updates.len(), 1);
+        assert!(result.self_trade_updates[0].new_order.isCancelled);
+    }
+
+    #[test]
+    fn test_stp_decrement_and_cancel_resting_smaller() {
+        let incoming = order(1, WETH, 10, USDC, 10, 5);
+        let book = vec![
+            order(1, USDC, 4, WETH, 4, 5),
+            order(2, USDC, 10, WETH, 10, 5),
+        ];
+
+        let result = match_order(
+            &incoming,
+            &book,
+            &with_stp(SelfTradePrevention::DecrementAndCancel),
+        )
+        .unwrap();
+        // 4 is decremented against the own order, the remaining 6 trade
+        assert_eq!(matched_indices(&result), vec![1]);
+        assert_eq!(result.fills[0].amount, U256::from(6));
+        assert!(result.new_order.isFilled);
+        assert!(!result.new_order.isCancelled);
+        let update = &result.self_trade_updates[0].new_order;
+        assert_eq!(update.amount_not_owned, U256::ZERO);
+        assert!(update.isCancelled);
+    }
+
+    #[test]
+    fn test_stp_decrement_and_cancel_incoming_smaller() {
+        let incoming = order(1, WETH, 4, USDC, 4, 5);
+        let book = vec![
+            order(1, USDC, 10, WETH, 10, 5),
+            order(2, USDC, 10, WETH, 10, 5),
+        ];
+
+        let result = match_order(
+            &incoming,
+            &book,
+            &with_stp(SelfTradePrevention::DecrementAndCancel),
+        )
+        .unwrap();
+        assert!(!result.is_match());
+        assert!(result.new_order.isCancelled);
+        assert_eq!(result.new_order.amount_not_owned, U256::ZERO);
+        let update = &result.self_trade_updates[0].new_order;
+        assert_eq!(update.amount_owned, U256::from(6));
+        assert_eq!(update.amount_not_owned, U256::from(6));
+        assert!(!update.isCancelled);
+        assert!(result.is_balanced(&incoming, &book));
+    }
+
+    #[test]
+    fn test_stp_not_triggered_by_non_crossing_own_order() {
+        let incoming = order(1, WETH, 5, USDC, 5, 5);
+        let book = vec![order(1, USDC, 5, WETH, 6, 5), order(2, USDC, 5, WETH, 5, 5)];
+
+        let result = match_order(&incoming, &book, &config()).unwrap();
+        assert_eq!(matched_indices(&result), vec![1]);
+        assert_eq!(result.self_trade, None);
+    }
+
+    #[test]
+    fn test_dormant_orders_are_never_matched() {
+        let stop = |mut order: Order| {
+            order.triggerType = TriggerType::Stop.into();
+            order.triggerPriceNumerator = U256::from(1);
+            order.triggerPriceDenominator = U256::from(1);
+            order
+        };
+
+        // A dormant incoming order rests as is
+        let book = vec![order(2, USDC, 5, WETH, 5, 5)];
+        let result = match_order(&stop(order(1, WETH, 5, USDC, 5, 5)), &book, &config()).unwrap();
+        assert!(!result.is_match());
+        assert_eq!(result.outcome(), MatchOutcome::Resting);
+
+        // Dormant resting orders are skipped until they have been triggered
+        let mut book = vec![
+            stop(order(2, USDC, 5, WETH, 5, 5)),
+            order(3, USDC, 5, WETH, 5, 5),
+        ];
+        let incoming = order(1, WETH, 5, USDC, 5, 5);
+        let result = match_order(&incoming, &book, &config()).unwrap();
+        assert_eq!(matched_indices(&result), vec![1]);
+
+        book[0].isTriggered = true;
+        let result = match_order(&incoming, &book, &config()).unwrap();
+        assert_eq!(matched_indices(&result), vec![0]);
+    }
+}
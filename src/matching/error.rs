@@ -0,0 +1,45 @@
+use alloy_primitives::U256;
+use thiserror::Error;
+
+/// Why the matching engine could not compute a result for an incoming order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum MatchError {
+    #[error("order has a zero amount")]
+    ZeroAmount,
+    #[error("order trades a token against itself")]
+    InvalidPair,
+    #[error("allocation references unknown order {0}")]
+    UnknownOrder(usize),
+    #[error("overflow while computing {0}")]
+    Overflow(&'static str),
+    #[error("underflow while computing {0}")]
+    Underflow(&'static str),
+}
+
+/// `a + b`, or [`MatchError::Overflow`] naming what was being computed.
+pub fn add(a: U256, b: U256, what: &'static str) -> Result<U256, MatchError> {
+    a.checked_add(b).ok_or(MatchError::Overflow(what))
+}
+
+/// `a - b`, or [`MatchError::Underflow`] naming what was being computed.
+pub fn sub(a: U256, b: U256, what: &'static str) -> Result<U256, MatchError> {
+    a.checked_sub(b).ok_or(MatchError::Underflow(what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checked_arithmetic() {
+        assert_eq!(add(U256::from(1), U256::from(2), "sum"), Ok(U256::from(3)));
+        assert_eq!(
+            add(U256::MAX, U256::from(1), "sum"),
+            Err(MatchError::Overflow("sum"))
+        );
+        assert_eq!(
+            sub(U256::ZERO, U256::from(1), "difference"),
+            Err(MatchError::Underflow("difference"))
+        );
+    }
+}
@@ -0,0 +1,59 @@
+use crate::IOrderBookTaskManager::Order;
+use alloy_primitives::U256;
+
+/// Returns true if the order has an expiry and it has passed at `now`.
+///
+/// An `expiry` of zero means the order is good-til-cancelled.
+pub fn is_expired(order: &Order, now: U256) -> bool {
+    !order.expiry.is_zero() && order.expiry <= now
+}
+
+/// Indices of the orders that are still resting in the book but have expired at `now`.
+pub fn expired_orders(orderbook: &[Order], now: U256) -> Vec<usize> {
+    orderbook
+        .iter()
+        .enumerate()
+        .filter(|(_, order)| !order.isFilled && !order.isCancelled && is_expired(order, now))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::test_support::{order, USDC, WETH};
+
+    /// An order expiring at `expiry`, zero meaning it never expires.
+    fn expiring(expiry: u64) -> Order {
+        Order {
+            expiry: U256::from(expiry),
+            ..order(0, WETH, 1, USDC, 1, 0)
+        }
+    }
+
+    #[test]
+    fn test_is_expired() {
+        assert!(!is_expired(&expiring(0), U256::MAX));
+        assert!(!is_expired(&expiring(100), U256::from(99)));
+        assert!(is_expired(&expiring(100), U256::from(100)));
+        assert!(is_expired(&expiring(100), U256::from(101)));
+    }
+
+    #[test]
+    fn test_expired_orders_skips_closed_orders() {
+        let mut filled = expiring(10);
+        filled.isFilled = true;
+        let mut cancelled = expiring(10);
+        cancelled.isCancelled = true;
+        let book = vec![
+            expiring(10),
+            expiring(0),
+            filled,
+            expiring(50),
+            cancelled,
+            expiring(20),
+        ];
+
+        assert_eq!(expired_orders(&book, U256::from(20)), vec![0, 5]);
+    }
+}
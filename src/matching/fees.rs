@@ -0,0 +1,84 @@
+use alloy_primitives::U256;
+
+/// Fee rates are quoted in basis points of the amount an order receives.
+pub const BPS_DENOMINATOR: u32 = 10_000;
+
+/// Maker and taker fee rates applied to every fill.
+///
+/// The maker is the resting order and pays its fee in the token it receives, the
+/// taker is the incoming order and likewise pays in the token it receives.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FeeSchedule {
+    maker_bps: u32,
+    taker_bps: u32,
+}
+
+impl FeeSchedule {
+    /// Returns `None` if either rate is above 100%.
+    pub fn new(maker_bps: u32, taker_bps: u32) -> Option<Self> {
+        if maker_bps > BPS_DENOMINATOR || taker_bps > BPS_DENOMINATOR {
+            return None;
+        }
+        Some(Self {
+            maker_bps,
+            taker_bps,
+        })
+    }
+
+    pub fn maker_bps(&self) -> u32 {
+        self.maker_bps
+    }
+
+    pub fn taker_bps(&self) -> u32 {
+        self.taker_bps
+    }
+
+    /// Fee charged to the resting order on `received`, rounded down.
+    pub fn maker_fee(&self, received: U256) -> U256 {
+        fee(received, self.maker_bps)
+    }
+
+    /// Fee charged to the incoming order on `received`, rounded down.
+    pub fn taker_fee(&self, received: U256) -> U256 {
+        fee(received, self.taker_bps)
+    }
+}
+
+/// `amount * bps / 10_000` without overflowing for any `amount`.
+fn fee(amount: U256, bps: u32) -> U256 {
+    let bps = U256::from(bps);
+    let denominator = U256::from(BPS_DENOMINATOR);
+    amount / denominator * bps + amount % denominator * bps / denominator
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fees() {
+        let fees = FeeSchedule::new(10, 25).unwrap();
+        assert_eq!(fees.maker_fee(U256::from(10_000)), U256::from(10));
+        assert_eq!(fees.taker_fee(U256::from(10_000)), U256::from(25));
+        // Rounded down in favour of the trader
+        assert_eq!(fees.taker_fee(U256::from(399)), U256::ZERO);
+        assert_eq!(fees.taker_fee(U256::from(401)), U256::from(1));
+        assert_eq!(FeeSchedule::default().maker_fee(U256::MAX), U256::ZERO);
+    }
+
+    #[test]
+    fn test_fee_does_not_overflow() {
+        let fees = FeeSchedule::new(BPS_DENOMINATOR, 1).unwrap();
+        assert_eq!(fees.maker_fee(U256::MAX), U256::MAX);
+        assert_eq!(
+            fees.taker_fee(U256::MAX),
+            U256::MAX / U256::from(BPS_DENOMINATOR)
+        );
+    }
+
+    #[test]
+    fn test_rates_above_one_hundred_percent_are_rejected() {
+        assert!(FeeSchedule::new(BPS_DENOMINATOR + 1, 0).is_none());
+        assert!(FeeSchedule::new(0, BPS_DENOMINATOR + 1).is_none());
+    }
+}
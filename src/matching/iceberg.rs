@@ -0,0 +1,154 @@
+use crate::IOrderBookTaskManager::Order;
+use alloy_primitives::U256;
+
+/// Returns true if only a slice of the order's size is shown in the book.
+///
+/// An iceberg order rests with `displayedAmount` of its `token_owned` visible and the
+/// rest held in reserve. A `displayQuantity` of zero means the whole order is shown.
+///
+/// Book views only show the slice: `getDisplayedOrders` returns each iceberg order cut
+/// down to its displayed part. Operators match the full order, so the contract stores it
+/// whole and its size can still be read from `orders`, `getAllOrders` and
+/// `NewTaskCreated`. Hiding the reserve from readers of the chain is out of scope.
+pub fn is_iceberg(order: &Order) -> bool {
+    !order.displayQuantity.is_zero()
+}
+
+/// The part of a resting order's `amount_owned` shown in the book, and the most it
+/// can trade before its slice has to be replenished. Continuous matching, rings and
+/// batch auctions all size resting orders with this.
+pub fn visible(order: &Order) -> U256 {
+    if is_iceberg(order) {
+        order.displayedAmount.min(order.amount_owned)
+    } else {
+        order.amount_owned
+    }
+}
+
+/// Takes `given` out of the displayed slice of a resting order, after its remaining
+/// amounts have been updated for the trade.
+///
+/// Once the slice runs out and a reserve is left, a new slice of `displayQuantity` is
+/// shown. The replenished slice joins the back of the queue at its price: its
+/// `timestamp` becomes `now`, the time of the trade that exhausted the previous one.
+pub fn consume(order: &mut Order, given: U256, now: U256) {
+    if !is_iceberg(order) {
+        return;
+    }
+    order.displayedAmount = order
+        .displayedAmount
+        .saturating_sub(given)
+        .min(order.amount_owned);
+    if order.displayedAmount.is_zero() && !order.isFilled && !order.isCancelled {
+        order.displayedAmount = order.displayQuantity.min(order.amount_owned);
+        order.timestamp = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::auction::clear;
+    use crate::matching::engine::match_order;
+    use crate::matching::market::Market;
+    use crate::matching::test_support::{config, order, USDC, WETH};
+
+    /// Shows `display` of the order's size at a time.
+    fn with_display(mut order: Order, display: u64) -> Order {
+        order.displayQuantity = U256::from(display);
+        order.displayedAmount = order.displayQuantity.min(order.amount_owned);
+        order
+    }
+
+    #[test]
+    fn test_only_the_slice_is_visible() {
+        let iceberg = with_display(order(1, USDC, 100, WETH, 100, 5), 30);
+        assert!(is_iceberg(&iceberg));
+        assert_eq!(visible(&iceberg), U256::from(30));
+
+        // A slice larger than what is left shows only what is left
+        let nearly_filled = Order {
+            amount_owned: U256::from(20),
+            ..iceberg
+        };
+        assert_eq!(visible(&nearly_filled), U256::from(20));
+
+        let plain = order(1, USDC, 100, WETH, 100, 5);
+        assert!(!is_iceberg(&plain));
+        assert_eq!(visible(&plain), U256::from(100));
+    }
+
+    #[test]
+    fn test_only_the_displayed_slice_trades() {
+        let book = vec![
+            with_display(order(1, USDC, 100, WETH, 100, 5), 30),
+            order(2, USDC, 50, WETH, 50, 5),
+        ];
+        let mut incoming = order(3, WETH, 40, USDC, 40, 5);
+        incoming.timestamp = U256::from(9);
+
+        let result = match_order(&incoming, &book, &config()).unwrap();
+        let fills: Vec<(usize, U256)> = result
+            .fills
+            .iter()
+            .map(|fill| (fill.index, fill.amount))
+            .collect();
+        assert_eq!(fills, vec![(0, U256::from(30)), (1, U256::from(10))]);
+        assert!(result.is_balanced(&incoming, &book));
+
+        // The exhausted slice is replenished from the reserve and loses its priority
+        let iceberg = &result.fills[0].new_order;
+        assert_eq!(iceberg.amount_owned, U256::from(70));
+        assert_eq!(iceberg.displayedAmount, U256::from(30));
+        assert_eq!(iceberg.timestamp, U256::from(9));
+    }
+
+    #[test]
+    fn test_the_reserve_is_not_matched_without_other_liquidity() {
+        // Nothing else rests in the book, the reserve still cannot be reached
+        let book = vec![with_display(order(1, USDC, 100, WETH, 100, 5), 30)];
+        let incoming = order(3, WETH, 80, USDC, 80, 5);
+
+        let result = match_order(&incoming, &book, &config()).unwrap();
+        assert_eq!(result.fills.len(), 1);
+        assert_eq!(result.fills[0].amount, U256::from(30));
+        assert_eq!(result.new_order.amount_owned, U256::from(50));
+    }
+
+    #[test]
+    fn test_only_the_displayed_slice_clears_in_a_batch() {
+        let market = Market::new(WETH, USDC).unwrap();
+        let book = vec![
+            with_display(order(1, USDC, 100, WETH, 100, 5), 30),
+            order(2, WETH, 80, USDC, 80, 5),
+        ];
+
+        let result = clear(market, &book, U256::from(9)).unwrap();
+        assert_eq!(result.volume, U256::from(30));
+        let iceberg = result
+            .updates
+            .iter()
+            .find(|update| update.index == 0)
+            .unwrap();
+        assert_eq!(iceberg.given, U256::from(30));
+        assert_eq!(iceberg.new_order.amount_owned, U256::from(70));
+        assert_eq!(iceberg.new_order.displayedAmount, U256::from(30));
+        assert!(result.is_balanced(&market, &book));
+    }
+
+    #[test]
+    fn test_partial_slice_keeps_priority() {
+        let mut iceberg = with_display(order(1, USDC, 100, WETH, 100, 5), 30);
+        iceberg.timestamp = U256::from(1);
+        iceberg.amount_owned = U256::from(90);
+        consume(&mut iceberg, U256::from(10), U256::from(9));
+        assert_eq!(iceberg.displayedAmount, U256::from(20));
+        assert_eq!(iceberg.timestamp, U256::from(1));
+
+        // The last slice is whatever is left in reserve
+        iceberg.amount_owned = U256::from(10);
+        consume(&mut iceberg, U256::from(20), U256::from(9));
+        assert_eq!(iceberg.displayedAmount, U256::from(10));
+        assert_eq!(iceberg.timestamp, U256::from(9));
+    }
+}
@@ -0,0 +1,123 @@
+use crate::IOrderBookTaskManager::Order;
+use alloy_primitives::Address;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The side of a market an order is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Side {
+    /// Gives `token1` to receive `token0`.
+    Bid,
+    /// Gives `token0` to receive `token1`.
+    Ask,
+}
+
+impl Side {
+    pub fn opposite(self) -> Self {
+        match self {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
+        }
+    }
+}
+
+/// A market keyed by the two tokens traded in it.
+///
+/// Tokens are stored in ascending address order, so both sides of a market share
+/// the same key regardless of which token each order owns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Market {
+    pub token0: Address,
+    pub token1: Address,
+}
+
+impl Market {
+    /// Creates the market for a pair of tokens, returning `None` if both tokens are the same.
+    pub fn new(token_a: Address, token_b: Address) -> Option<Self> {
+        match token_a.cmp(&token_b) {
+            std::cmp::Ordering::Less => Some(Self {
+                token0: token_a,
+                token1: token_b,
+            }),
+            std::cmp::Ordering::Greater => Some(Self {
+                token0: token_b,
+                token1: token_a,
+            }),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+
+    /// The market an order trades in.
+    pub fn of(order: &Order) -> Option<Self> {
+        Self::new(order.token_owned, order.token_not_owned)
+    }
+
+    /// The side of this market an order is on, or `None` if it trades in another market.
+    pub fn side(&self, order: &Order) -> Option<Side> {
+        if order.token_owned == self.token1 && order.token_not_owned == self.token0 {
+            Some(Side::Bid)
+        } else if order.token_owned == self.token0 && order.token_not_owned == self.token1 {
+            Some(Side::Ask)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Market {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.token0, self.token1)
+    }
+}
+
+/// Returns true if both orders trade the same pair of tokens on opposite sides.
+pub fn is_counterparty(order: &Order, other: &Order) -> bool {
+    order.token_owned != order.token_not_owned
+        && order.token_owned == other.token_not_owned
+        && order.token_not_owned == other.token_owned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::test_support::{order, DAI, USDC, WETH};
+
+    #[test]
+    fn test_market_is_independent_of_token_order() {
+        assert_eq!(Market::new(WETH, USDC), Market::new(USDC, WETH));
+        assert_eq!(Market::new(WETH, WETH), None);
+    }
+
+    #[test]
+    fn test_sides() {
+        let market = Market::new(WETH, USDC).unwrap();
+        assert_eq!(market.side(&order(0, USDC, 1, WETH, 1, 0)), Some(Side::Bid));
+        assert_eq!(market.side(&order(0, WETH, 1, USDC, 1, 0)), Some(Side::Ask));
+        assert_eq!(market.side(&order(0, DAI, 1, WETH, 1, 0)), None);
+        assert_eq!(Side::Bid.opposite(), Side::Ask);
+    }
+
+    #[test]
+    fn test_counterparty_requires_same_pair_on_opposite_sides() {
+        assert!(is_counterparty(
+            &order(0, WETH, 1, USDC, 1, 0),
+            &order(0, USDC, 1, WETH, 1, 0)
+        ));
+        assert!(!is_counterparty(
+            &order(0, WETH, 1, USDC, 1, 0),
+            &order(0, WETH, 1, USDC, 1, 0)
+        ));
+        assert!(!is_counterparty(
+            &order(0, WETH, 1, USDC, 1, 0),
+            &order(0, DAI, 1, WETH, 1, 0)
+        ));
+        assert!(!is_counterparty(
+            &order(0, WETH, 1, USDC, 1, 0),
+            &order(0, USDC, 1, DAI, 1, 0)
+        ));
+        assert!(!is_counterparty(
+            &order(0, WETH, 1, WETH, 1, 0),
+            &order(0, WETH, 1, WETH, 1, 0)
+        ));
+    }
+}
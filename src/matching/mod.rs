@@ -0,0 +1,19 @@
+pub mod amend;
+pub mod auction;
+pub mod book;
+pub mod engine;
+pub mod error;
+pub mod expiry;
+pub mod fees;
+pub mod iceberg;
+pub mod market;
+pub mod order_type;
+pub mod price;
+pub mod ring;
+pub mod spec;
+pub mod stp;
+pub mod strategy;
+pub mod trigger;
+
+#[cfg(test)]
+pub(crate) mod test_support;
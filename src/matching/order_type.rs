@@ -0,0 +1,76 @@
+use crate::IOrderBookTaskManager::Order;
+use serde::{Deserialize, Serialize};
+
+/// How an order interacts with the book, as encoded in `Order.orderType`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum OrderType {
+    /// Fills at its limit price or better and rests for the remainder.
+    #[default]
+    Limit = 0,
+    /// Fills at any price within its slippage and never rests.
+    Market = 1,
+    /// Fills at its limit price or better and cancels the remainder.
+    ImmediateOrCancel = 2,
+    /// Fills completely at its limit price or better, or not at all.
+    FillOrKill = 3,
+    /// Only ever rests in the book; rejected if it would fill on arrival.
+    PostOnly = 4,
+}
+
+impl OrderType {
+    /// The type of an order, or `None` if the order carries an unknown type.
+    pub fn of(order: &Order) -> Option<Self> {
+        Self::try_from(order.orderType).ok()
+    }
+
+    /// Whether the unfilled part of an order of this type stays in the book.
+    pub fn can_rest(self) -> bool {
+        matches!(self, OrderType::Limit | OrderType::PostOnly)
+    }
+
+    /// Whether resting orders must be at or better than the order's own price to match.
+    pub fn has_limit_price(self) -> bool {
+        !matches!(self, OrderType::Market)
+    }
+}
+
+impl TryFrom<u8> for OrderType {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(OrderType::Limit),
+            1 => Ok(OrderType::Market),
+            2 => Ok(OrderType::ImmediateOrCancel),
+            3 => Ok(OrderType::FillOrKill),
+            4 => Ok(OrderType::PostOnly),
+            other => Err(other),
+        }
+    }
+}
+
+impl From<OrderType> for u8 {
+    fn from(value: OrderType) -> Self {
+        value as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips_through_u8() {
+        for order_type in [
+            OrderType::Limit,
+            OrderType::Market,
+            OrderType::ImmediateOrCancel,
+            OrderType::FillOrKill,
+            OrderType::PostOnly,
+        ] {
+            assert_eq!(OrderType::try_from(u8::from(order_type)), Ok(order_type));
+        }
+        assert_eq!(OrderType::try_from(5), Err(5));
+    }
+}
@@ -0,0 +1,244 @@
+use crate::IOrderBookTaskManager::Order;
+use alloy_primitives::{ruint::Uint, U256, U512};
+use std::cmp::Ordering;
+
+/// Wide enough to hold the product of two `U256` values scaled by a percentage
+/// without overflowing.
+type Wide = Uint<768, 12>;
+
+/// An exact price expressed as the ratio `numerator / denominator`.
+///
+/// Prices are never reduced to an integer, so comparisons are done by
+/// cross-multiplication and never lose precision.
+#[derive(Debug, Clone, Copy)]
+pub struct Price {
+    numerator: U256,
+    denominator: U256,
+}
+
+impl Price {
+    /// Creates a new price, returning `None` if either side of the ratio is zero.
+    pub fn new(numerator: U256, denominator: U256) -> Option<Self> {
+        if numerator.is_zero() || denominator.is_zero() {
+            return None;
+        }
+        Some(Self {
+            numerator,
+            denominator,
+        })
+    }
+
+    /// The limit price of an order: how much of `token_owned` the user is willing
+    /// to give for one unit of `token_not_owned`.
+    pub fn of(order: &Order) -> Option<Self> {
+        Self::new(order.amount_owned, order.amount_not_owned)
+    }
+
+    pub fn numerator(&self) -> U256 {
+        self.numerator
+    }
+
+    pub fn denominator(&self) -> U256 {
+        self.denominator
+    }
+
+    /// The same price quoted from the other side of the pair.
+    pub fn inverse(self) -> Self {
+        Self {
+            numerator: self.denominator,
+            denominator: self.numerator,
+        }
+    }
+
+    /// The price halfway between two prices, or `None` if it cannot be represented.
+    pub fn midpoint(self, other: Price) -> Option<Self> {
+        // (a/b + c/d) / 2 = (ad + cb) / 2bd
+        let numerator = self
+            .numerator
+            .checked_mul(other.denominator)?
+            .checked_add(other.numerator.checked_mul(self.denominator)?)?;
+        let denominator = self
+            .denominator
+            .checked_mul(other.denominator)?
+            .checked_mul(U256::from(2))?;
+        Self::new(numerator, denominator)
+    }
+
+    /// The same price in whole tokens rather than base units, given the decimals of
+    /// the numerator and denominator tokens, or `None` if it cannot be represented.
+    ///
+    /// Both sides of a market are scaled by the same factor, so normalizing never
+    /// changes how two prices of the same pair compare.
+    pub fn normalize(self, numerator_decimals: u8, denominator_decimals: u8) -> Option<Self> {
+        // (a / 10^n) / (b / 10^d) = a * 10^d / (b * 10^n)
+        let ten = U256::from(10);
+        if denominator_decimals >= numerator_decimals {
+            let scale = ten.checked_pow(U256::from(denominator_decimals - numerator_decimals))?;
+            Self::new(self.numerator.checked_mul(scale)?, self.denominator)
+        } else {
+            let scale = ten.checked_pow(U256::from(numerator_decimals - denominator_decimals))?;
+            Self::new(self.numerator, self.denominator.checked_mul(scale)?)
+        }
+    }
+
+    /// Decimal representation of the price, truncated to `precision` fractional digits.
+    pub fn to_decimal_string(&self, precision: usize) -> String {
+        let ten = U256::from(10);
+        let mut decimal = (self.numerator / self.denominator).to_string();
+        let mut remainder = self.numerator % self.denominator;
+
+        if precision > 0 && !remainder.is_zero() {
+            decimal.push('.');
+            for _ in 0..precision {
+                if remainder.is_zero() {
+                    break;
+                }
+                // remainder < denominator, so the product fits in a U512
+                let shifted = U512::from(remainder) * U512::from(ten);
+                let denominator = U512::from(self.denominator);
+                decimal.push_str(&(shifted / denominator).to_string());
+                remainder = U256::from(shifted % denominator);
+            }
+        }
+        decimal
+    }
+
+    /// `amount * price`, rounded up, or `None` if the result does not fit in a `U256`.
+    pub fn mul_ceil(&self, amount: U256) -> Option<U256> {
+        let product = U512::from(amount) * U512::from(self.numerator);
+        let denominator = U512::from(self.denominator);
+        narrow((product + denominator - U512::from(1)) / denominator)
+    }
+
+    /// `amount / price`, rounded down, or `None` if the result does not fit in a `U256`.
+    pub fn div_floor(&self, amount: U256) -> Option<U256> {
+        narrow(U512::from(amount) * U512::from(self.denominator) / U512::from(self.numerator))
+    }
+
+    /// Returns true if the difference between the two prices, as a percentage of
+    /// their midpoint, is strictly greater than `percent`.
+    pub fn deviation_exceeds(self, other: Price, percent: U256) -> bool {
+        let (lhs, rhs) = self.cross(&other);
+        let diff = if lhs >= rhs { lhs - rhs } else { rhs - lhs };
+        // |a/b - c/d| / ((a/b + c/d) / 2) * 100 > percent
+        diff * Wide::from(200u64) > (lhs + rhs).saturating_mul(Wide::from(percent))
+    }
+
+    /// Cross-multiplies two prices so that `self.cmp(other) == lhs.cmp(rhs)`.
+    fn cross(&self, other: &Price) -> (Wide, Wide) {
+        (
+            Wide::from(self.numerator) * Wide::from(other.denominator),
+            Wide::from(other.numerator) * Wide::from(self.denominator),
+        )
+    }
+}
+
+pub(crate) fn narrow(value: U512) -> Option<U256> {
+    (value <= U512::from(U256::MAX)).then(|| U256::from(value))
+}
+
+impl PartialEq for Price {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (lhs, rhs) = self.cross(other);
+        lhs.cmp(&rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(numerator: u64, denominator: u64) -> Price {
+        Price::new(U256::from(numerator), U256::from(denominator)).unwrap()
+    }
+
+    #[test]
+    fn test_zero_price_is_rejected() {
+        assert!(Price::new(U256::ZERO, U256::from(1)).is_none());
+        assert!(Price::new(U256::from(1), U256::ZERO).is_none());
+    }
+
+    #[test]
+    fn test_compares_without_truncation() {
+        // Both of these truncate to zero with integer division.
+        assert!(price(5, 200) < price(6, 190));
+        assert!(price(6, 190) > price(5, 200));
+        assert_eq!(price(5, 200), price(10, 400));
+    }
+
+    #[test]
+    fn test_compares_large_values() {
+        let max = Price::new(U256::MAX, U256::from(1)).unwrap();
+        let almost_max = Price::new(U256::MAX - U256::from(1), U256::from(1)).unwrap();
+        assert!(almost_max < max);
+        assert!(max.inverse() < almost_max.inverse());
+    }
+
+    #[test]
+    fn test_midpoint() {
+        assert_eq!(price(1, 2).midpoint(price(1, 1)), Some(price(3, 4)));
+        assert_eq!(price(5, 200).midpoint(price(5, 200)), Some(price(5, 200)));
+        assert!(Price::new(U256::MAX, U256::from(1))
+            .unwrap()
+            .midpoint(price(1, 2))
+            .is_none());
+    }
+
+    #[test]
+    fn test_rounding() {
+        assert_eq!(price(1, 3).mul_ceil(U256::from(10)), Some(U256::from(4)));
+        assert_eq!(price(1, 2).mul_ceil(U256::from(10)), Some(U256::from(5)));
+        assert_eq!(price(1, 3).div_floor(U256::from(10)), Some(U256::from(30)));
+        assert_eq!(price(3, 1).div_floor(U256::from(10)), Some(U256::from(3)));
+        assert_eq!(price(2, 1).mul_ceil(U256::MAX), None);
+        assert_eq!(price(1, 2).div_floor(U256::MAX), None);
+    }
+
+    #[test]
+    fn test_normalize() {
+        // 2000 USDC (6 decimals) per WETH (18 decimals), in base units
+        let usdc_per_weth =
+            Price::new(U256::from(2_000_000_000u64), U256::from(10u64.pow(18))).unwrap();
+        assert_eq!(usdc_per_weth.normalize(6, 18), Some(price(2000, 1)));
+        assert_eq!(
+            usdc_per_weth.inverse().normalize(18, 6),
+            Some(price(1, 2000))
+        );
+        assert_eq!(price(5, 200).normalize(6, 6), Some(price(5, 200)));
+        assert!(Price::new(U256::MAX, U256::from(1))
+            .unwrap()
+            .normalize(0, 1)
+            .is_none());
+    }
+
+    #[test]
+    fn test_to_decimal_string() {
+        assert_eq!(price(2000, 1).to_decimal_string(4), "2000");
+        assert_eq!(price(1, 2000).to_decimal_string(4), "0.0005");
+        assert_eq!(price(1, 3).to_decimal_string(4), "0.3333");
+        assert_eq!(price(7, 2).to_decimal_string(0), "3");
+    }
+
+    #[test]
+    fn test_deviation() {
+        // 5/200 and 5/210 are ~4.9% apart.
+        assert!(!price(5, 200).deviation_exceeds(price(5, 210), U256::from(5)));
+        assert!(price(5, 200).deviation_exceeds(price(5, 210), U256::from(4)));
+        assert!(!price(5, 200).deviation_exceeds(price(10, 400), U256::ZERO));
+        assert!(!price(1, 1).deviation_exceeds(price(1, 1000), U256::MAX));
+    }
+}
@@ -0,0 +1,492 @@
+use crate::matching::auction::BatchMarkets;
+use crate::matching::error::{add, sub, MatchError};
+use crate::matching::expiry::is_expired;
+use crate::matching::iceberg::{consume, visible};
+use crate::matching::market::Market;
+use crate::matching::order_type::OrderType;
+use crate::matching::price::Price;
+use crate::matching::trigger::is_dormant;
+use crate::IOrderBookTaskManager::Order;
+use alloy_primitives::{Address, U256, U512};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// A resting order filled as one hop of a ring.
+#[derive(Debug, Clone)]
+pub struct RingFill {
+    /// Index of the resting order in the orderbook.
+    pub index: usize,
+    /// The resting order after the ring has been settled.
+    pub new_order: Order,
+    /// Amount of `token_owned` the resting order gave.
+    pub given: U256,
+    /// Amount of `token_not_owned` the resting order received.
+    pub received: U256,
+}
+
+/// A cycle of orders that fill each other even though no two of them match.
+///
+/// The first hop gives the incoming order its `token_not_owned`, every following
+/// hop gives the previous one what it wants, and the incoming order pays the last
+/// hop in its `token_owned`.
+#[derive(Debug, Clone)]
+pub struct Ring {
+    pub hops: Vec<RingFill>,
+    /// Amount of `token_owned` the incoming order gave.
+    pub given: U256,
+    /// Amount of `token_not_owned` the incoming order received.
+    pub received: U256,
+}
+
+impl Ring {
+    /// Returns true if every order receives exactly what the next order in the cycle
+    /// gives, and every hop gave and received what its update says.
+    pub fn is_balanced(&self, orderbook: &[Order]) -> bool {
+        let Some(last) = self.hops.last() else {
+            return false;
+        };
+        let mut owed = self.received;
+        for hop in &self.hops {
+            let Some(order) = orderbook.get(hop.index) else {
+                return false;
+            };
+            if hop.given != owed
+                || order.amount_owned.checked_sub(hop.new_order.amount_owned) != Some(hop.given)
+                || order
+                    .amount_not_owned
+                    .checked_sub(hop.new_order.amount_not_owned)
+                    != Some(hop.received)
+            {
+                return false;
+            }
+            owed = hop.received;
+        }
+        last.received == self.given
+    }
+}
+
+/// What is left of the incoming order for ring matching.
+#[derive(Debug, Clone, Copy)]
+pub struct RingBudget {
+    /// Amount of `token_not_owned` the incoming order still wants.
+    pub remaining: U256,
+    /// Amount of `token_owned` the incoming order can still give.
+    pub budget: U256,
+    /// Maximum number of resting orders all rings may use together.
+    pub max_fills: usize,
+}
+
+/// Fills an incoming order through rings of resting orders over three or more tokens.
+///
+/// Rings are found one at a time over the orderbook as updated by the rings before
+/// them. Each ring is the one with the best rate for the incoming order, then the one
+/// giving it the most, then the one with the fewest hops, then the one with the lowest
+/// resting order indices, so every operator settles the same rings. A ring has at
+/// most `max_length` orders including the incoming order.
+///
+/// Every resting order trades at its own limit price, rounded in its favour, and the
+/// whole surplus of the ring goes to the incoming order. The incoming order's limit
+/// price, unless it is a market order, and its `slippage` apply to the effective
+/// price it pays across the ring. Resting orders of the incoming order's user are
+/// never part of a ring, and ring fills are not charged fees. Resting orders of
+/// `batch_markets` only trade in their market's batch auction, so they are never part
+/// of a ring either.
+pub fn match_rings(
+    order: &Order,
+    orderbook: &[Order],
+    budget: RingBudget,
+    max_length: usize,
+    batch_markets: &BatchMarkets,
+) -> Result<Vec<Ring>, MatchError> {
+    let mut rings = Vec::new();
+    if max_length < 3 {
+        return Ok(rings);
+    }
+    let Some(price_for_user) = Price::of(order) else {
+        return Ok(rings);
+    };
+    let has_limit_price = OrderType::of(order).is_some_and(OrderType::has_limit_price);
+
+    let mut book = orderbook.to_vec();
+    let (mut remaining, mut left, mut fills_left) =
+        (budget.remaining, budget.budget, budget.max_fills);
+
+    while !remaining.is_zero() && !left.is_zero() && fills_left >= 2 {
+        let search = Search {
+            order,
+            book: &book,
+            by_token: eligible(order, &book, batch_markets),
+            remaining,
+            budget: left,
+            max_hops: (max_length - 1).min(fills_left),
+        };
+        let mut best: Option<Ring> = None;
+        let mut path = Vec::new();
+        search.visit(
+            order.token_not_owned,
+            &mut vec![order.token_owned, order.token_not_owned],
+            &mut path,
+            &mut best,
+        )?;
+        let Some(ring) = best else {
+            break;
+        };
+
+        // The incoming order's limit and slippage apply to the ring as a whole
+        let effective = Price::new(ring.given, ring.received).ok_or(MatchError::ZeroAmount)?;
+        if has_limit_price && effective > price_for_user {
+            break;
+        }
+        if price_for_user.deviation_exceeds(effective, order.slippage) {
+            break;
+        }
+
+        remaining = sub(remaining, ring.received, "ring remainder")?;
+        left = sub(left, ring.given, "ring budget")?;
+        fills_left -= ring.hops.len();
+        for hop in &ring.hops {
+            book[hop.index] = hop.new_order.clone();
+        }
+        rings.push(ring);
+    }
+
+    Ok(rings)
+}
+
+/// Live resting orders that may take part in a ring, by the token they give.
+fn eligible(
+    order: &Order,
+    book: &[Order],
+    batch_markets: &BatchMarkets,
+) -> HashMap<Address, Vec<usize>> {
+    let mut by_token: HashMap<Address, Vec<usize>> = HashMap::new();
+    for (index, other_order) in book.iter().enumerate() {
+        if other_order.isFilled
+            || other_order.isCancelled
+            || is_dormant(other_order)
+            || other_order.user == order.user
+            || is_expired(other_order, order.timestamp)
+            || !OrderType::of(other_order).is_some_and(OrderType::can_rest)
+            || Price::of(other_order).is_none()
+            || Market::of(other_order).is_some_and(|market| batch_markets.contains(&market))
+        {
+            continue;
+        }
+        by_token
+            .entry(other_order.token_owned)
+            .or_default()
+            .push(index);
+    }
+    by_token
+}
+
+/// Depth-first search for the best ring through the incoming order.
+struct Search<'a> {
+    order: &'a Order,
+    book: &'a [Order],
+    by_token: HashMap<Address, Vec<usize>>,
+    remaining: U256,
+    budget: U256,
+    max_hops: usize,
+}
+
+impl Search<'_> {
+    /// Extends `path`, whose last order wants `wanted`, by every order giving it.
+    fn visit(
+        &self,
+        wanted: Address,
+        tokens: &mut Vec<Address>,
+        path: &mut Vec<usize>,
+        best: &mut Option<Ring>,
+    ) -> Result<(), MatchError> {
+        let Some(candidates) = self.by_token.get(&wanted) else {
+            return Ok(());
+        };
+        for &index in candidates {
+            if path.contains(&index) {
+                continue;
+            }
+            let next = self.book[index].token_not_owned;
+            path.push(index);
+
+            // Closing the cycle after a single resting order would be a pairwise match
+            if next == self.order.token_owned && path.len() >= 2 {
+                if let Some(ring) = self.settle(path)? {
+                    if best.as_ref().map_or(true, |best| is_better(&ring, best)) {
+                        *best = Some(ring);
+                    }
+                }
+            } else if !tokens.contains(&next) && path.len() < self.max_hops {
+                tokens.push(next);
+                self.visit(next, tokens, path, best)?;
+                tokens.pop();
+            }
+
+            path.pop();
+        }
+        Ok(())
+    }
+
+    /// Settles the largest amount that can flow around `path`, if any.
+    fn settle(&self, path: &[usize]) -> Result<Option<Ring>, MatchError> {
+        // Each hop trades at its own price, the amount it wants per amount it gives
+        let prices = path
+            .iter()
+            .map(|&index| {
+                Price::of(&self.book[index])
+                    .map(Price::inverse)
+                    .ok_or(MatchError::ZeroAmount)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Walk backwards from what the incoming order can pay to the largest amount the
+        // first hop can give without any hop running out
+        let mut limit = self.budget;
+        for (&index, price) in path.iter().zip(&prices).rev() {
+            limit = price
+                .div_floor(limit)
+                .ok_or(MatchError::Overflow("ring capacity"))?
+                .min(visible(&self.book[index]));
+        }
+        let received = limit.min(self.remaining);
+        if received.is_zero() {
+            return Ok(None);
+        }
+
+        // Walk forwards, every hop receives what it gives at its price, rounded up
+        let mut hops = Vec::with_capacity(path.len());
+        let mut given = received;
+        for (&index, price) in path.iter().zip(&prices) {
+            let other_order = &self.book[index];
+            let paid = price
+                .mul_ceil(given)
+                .ok_or(MatchError::Overflow("ring payment"))?
+                .min(other_order.amount_not_owned);
+            if paid.is_zero() {
+                return Ok(None);
+            }
+
+            let mut new_order = other_order.clone();
+            new_order.amount_owned = sub(other_order.amount_owned, given, "ring hop remainder")?;
+            new_order.amount_not_owned =
+                sub(other_order.amount_not_owned, paid, "ring hop remainder")?;
+            new_order.timestamp_matched = self.order.timestamp;
+            if new_order.amount_owned.is_zero() || new_order.amount_not_owned.is_zero() {
+                new_order.isFilled = true;
+            } else {
+                new_order.isPartiallyFilled = true;
+            }
+            consume(&mut new_order, given, self.order.timestamp);
+            hops.push(RingFill {
+                index,
+                new_order,
+                given,
+                received: paid,
+            });
+            given = paid;
+        }
+        if given > self.budget {
+            return Ok(None);
+        }
+
+        Ok(Some(Ring {
+            hops,
+            given,
+            received,
+        }))
+    }
+}
+
+/// Ranks rings by what the incoming order receives per unit given, then the amount
+/// it receives, then the number of hops, then the resting order indices.
+fn is_better(ring: &Ring, best: &Ring) -> bool {
+    // received / given, compared by cross-multiplication
+    let rate = U512::from(ring.received) * U512::from(best.given);
+    let best_rate = U512::from(best.received) * U512::from(ring.given);
+    let indices = |ring: &Ring| ring.hops.iter().map(|hop| hop.index).collect::<Vec<_>>();
+    rate.cmp(&best_rate)
+        .then(ring.received.cmp(&best.received))
+        .then(best.hops.len().cmp(&ring.hops.len()))
+        .then(indices(best).cmp(&indices(ring)))
+        == Ordering::Greater
+}
+
+/// Total amounts the incoming order gave and received across `rings`.
+pub fn ring_totals(rings: &[Ring]) -> Result<(U256, U256), MatchError> {
+    rings
+        .iter()
+        .try_fold((U256::ZERO, U256::ZERO), |(given, received), ring| {
+            Ok((
+                add(given, ring.given, "ring totals")?,
+                add(received, ring.received, "ring totals")?,
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::test_support::{order, DAI, USDC, WBTC, WETH};
+
+    fn budget(order: &Order) -> RingBudget {
+        RingBudget {
+            remaining: order.amount_not_owned,
+            budget: order.amount_owned,
+            max_fills: 16,
+        }
+    }
+
+    /// Rings of at most `max_length` orders, without batch auction markets.
+    fn find_rings(incoming: &Order, book: &[Order], max_length: usize) -> Vec<Ring> {
+        match_rings(
+            incoming,
+            book,
+            budget(incoming),
+            max_length,
+            &BatchMarkets::default(),
+        )
+        .unwrap()
+    }
+
+    fn indices(ring: &Ring) -> Vec<usize> {
+        ring.hops.iter().map(|hop| hop.index).collect()
+    }
+
+    #[test]
+    fn test_three_token_ring() {
+        // WETH -> USDC, USDC -> DAI and DAI -> WETH, no two of them match
+        let incoming = order(1, WETH, 10, USDC, 100, 100);
+        let book = vec![
+            order(2, USDC, 100, DAI, 100, 100),
+            order(3, DAI, 100, WETH, 10, 100),
+        ];
+
+        let rings = find_rings(&incoming, &book, 3);
+        assert_eq!(rings.len(), 1);
+        let ring = &rings[0];
+        assert_eq!(indices(ring), vec![0, 1]);
+        assert_eq!(ring.received, U256::from(100));
+        assert_eq!(ring.given, U256::from(10));
+        assert!(ring.is_balanced(&book));
+        assert!(ring.hops.iter().all(|hop| hop.new_order.isFilled));
+    }
+
+    #[test]
+    fn test_surplus_goes_to_the_incoming_order() {
+        // The incoming order would pay 10 WETH but the ring only asks for 8
+        let incoming = order(1, WETH, 10, USDC, 100, 100);
+        let book = vec![
+            order(2, USDC, 100, DAI, 100, 100),
+            order(3, DAI, 100, WETH, 8, 100),
+        ];
+
+        let rings = find_rings(&incoming, &book, 3);
+        assert_eq!(rings[0].given, U256::from(8));
+        assert_eq!(rings[0].received, U256::from(100));
+        assert!(rings[0].is_balanced(&book));
+    }
+
+    #[test]
+    fn test_smallest_capacity_limits_the_ring() {
+        let incoming = order(1, WETH, 10, USDC, 100, 100);
+        let book = vec![
+            order(2, USDC, 100, DAI, 100, 100),
+            order(3, DAI, 40, WETH, 4, 100),
+        ];
+
+        let rings = find_rings(&incoming, &book, 3);
+        let ring = &rings[0];
+        assert_eq!(ring.received, U256::from(40));
+        assert_eq!(ring.given, U256::from(4));
+        assert!(ring.hops[0].new_order.isPartiallyFilled);
+        assert!(ring.hops[1].new_order.isFilled);
+        assert!(ring.is_balanced(&book));
+    }
+
+    #[test]
+    fn test_unprofitable_rings_are_skipped() {
+        // The ring asks for 20 WETH, more than the incoming order is willing to pay
+        let incoming = order(1, WETH, 10, USDC, 100, 100);
+        let book = vec![
+            order(2, USDC, 100, DAI, 100, 100),
+            order(3, DAI, 100, WETH, 20, 100),
+        ];
+        assert!(find_rings(&incoming, &book, 3).is_empty());
+    }
+
+    #[test]
+    fn test_longer_rings_respect_max_length() {
+        let incoming = order(1, WETH, 10, USDC, 100, 100);
+        let book = vec![
+            order(2, USDC, 100, DAI, 100, 100),
+            order(3, DAI, 100, WBTC, 1, 100),
+            order(4, WBTC, 1, WETH, 10, 100),
+        ];
+
+        assert!(find_rings(&incoming, &book, 3).is_empty());
+        let rings = find_rings(&incoming, &book, 4);
+        assert_eq!(indices(&rings[0]), vec![0, 1, 2]);
+        assert!(rings[0].is_balanced(&book));
+    }
+
+    #[test]
+    fn test_selection_is_deterministic() {
+        // Two equivalent rings, the one with the lowest indices is settled first and
+        // the other one fills the rest
+        let incoming = order(1, WETH, 10, USDC, 100, 100);
+        let book = vec![
+            order(2, USDC, 50, DAI, 50, 100),
+            order(3, DAI, 50, WETH, 5, 100),
+            order(4, USDC, 50, DAI, 50, 100),
+            order(5, DAI, 50, WETH, 5, 100),
+        ];
+
+        let rings = find_rings(&incoming, &book, 3);
+        assert_eq!(rings.len(), 2);
+        assert_eq!(indices(&rings[0]), vec![0, 1]);
+        assert_eq!(indices(&rings[1]), vec![2, 3]);
+        assert_eq!(
+            ring_totals(&rings).unwrap(),
+            (U256::from(10), U256::from(100))
+        );
+    }
+
+    #[test]
+    fn test_better_rate_wins() {
+        // The second DAI seller asks for less WETH, so its ring is settled
+        let incoming = order(1, WETH, 10, USDC, 100, 100);
+        let book = vec![
+            order(2, USDC, 100, DAI, 100, 100),
+            order(3, DAI, 100, WETH, 10, 100),
+            order(4, DAI, 100, WETH, 9, 100),
+        ];
+
+        let rings = find_rings(&incoming, &book, 3);
+        assert_eq!(indices(&rings[0]), vec![0, 2]);
+        assert_eq!(rings[0].given, U256::from(9));
+    }
+
+    #[test]
+    fn test_own_orders_are_never_part_of_a_ring() {
+        let incoming = order(1, WETH, 10, USDC, 100, 100);
+        let book = vec![
+            order(1, USDC, 100, DAI, 100, 100),
+            order(3, DAI, 100, WETH, 10, 100),
+        ];
+        assert!(find_rings(&incoming, &book, 3).is_empty());
+    }
+
+    #[test]
+    fn test_batch_auction_markets_are_never_part_of_a_ring() {
+        let incoming = order(1, WETH, 10, USDC, 100, 100);
+        let book = vec![
+            order(2, USDC, 100, DAI, 100, 100),
+            order(3, DAI, 100, WETH, 10, 100),
+        ];
+        let mut batch_markets = BatchMarkets::default();
+        batch_markets.insert(Market::new(USDC, DAI).unwrap());
+
+        let rings = match_rings(&incoming, &book, budget(&incoming), 3, &batch_markets);
+        assert!(rings.unwrap().is_empty());
+    }
+}
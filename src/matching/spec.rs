@@ -0,0 +1,317 @@
+use crate::matching::market::{Market, Side};
+use crate::matching::order_type::OrderType;
+use crate::matching::price::Price;
+use crate::Error;
+use crate::IOrderBookTaskManager::Order;
+use alloy_primitives::{Address, U256, U512};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Why an incoming order was rejected by the specification of its market.
+///
+/// The discriminant is the code reported in `TaskResponse.rejectionReason`, where
+/// zero means the order was not rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Rejection {
+    /// The order trades less of the base token than the market minimum.
+    BelowMinSize = 1,
+    /// The base token amount is not a multiple of the lot size.
+    InvalidLotSize = 2,
+    /// The limit price is not a multiple of the tick size.
+    InvalidTickSize = 3,
+    /// The quote token amount is below the market minimum.
+    BelowMinNotional = 4,
+}
+
+impl Rejection {
+    /// Code reported in the `TaskResponse` for this rejection.
+    pub fn code(rejection: Option<Self>) -> u8 {
+        rejection.map_or(0, |rejection| rejection as u8)
+    }
+}
+
+/// Granularity rules of a single market.
+///
+/// Sizes are measured in base units of the market's `token0` and notionals in base
+/// units of `token1`. The tick size is a price in `token1` per `token0` base unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarketSpec {
+    pub min_size: U256,
+    pub lot_size: U256,
+    pub tick_size: Option<Price>,
+    pub min_notional: U256,
+}
+
+impl Default for MarketSpec {
+    /// A specification that accepts every order.
+    fn default() -> Self {
+        Self {
+            min_size: U256::ZERO,
+            lot_size: U256::from(1),
+            tick_size: None,
+            min_notional: U256::ZERO,
+        }
+    }
+}
+
+impl MarketSpec {
+    /// Checks an order of `market` against this specification.
+    ///
+    /// Orders without a limit price, i.e. market orders, are not subject to the tick size.
+    pub fn validate(&self, market: &Market, order: &Order) -> Result<(), Rejection> {
+        // Asks give the base token, bids receive it
+        let (size, notional) = match market.side(order) {
+            Some(Side::Ask) => (order.amount_owned, order.amount_not_owned),
+            Some(Side::Bid) => (order.amount_not_owned, order.amount_owned),
+            None => return Ok(()),
+        };
+
+        if size < self.min_size {
+            return Err(Rejection::BelowMinSize);
+        }
+        if !self.lot_size.is_zero() && !(size % self.lot_size).is_zero() {
+            return Err(Rejection::InvalidLotSize);
+        }
+        if notional < self.min_notional {
+            return Err(Rejection::BelowMinNotional);
+        }
+
+        let has_limit_price = OrderType::of(order).is_some_and(OrderType::has_limit_price);
+        if let (Some(tick), true) = (self.tick_size, has_limit_price) {
+            if !is_multiple_of_tick(notional, size, tick) {
+                return Err(Rejection::InvalidTickSize);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns true if `notional / size` is a whole number of ticks.
+fn is_multiple_of_tick(notional: U256, size: U256, tick: Price) -> bool {
+    // (notional / size) / (n / d) = notional * d / (size * n)
+    let dividend = U512::from(notional) * U512::from(tick.denominator());
+    let divisor = U512::from(size) * U512::from(tick.numerator());
+    !divisor.is_zero() && (dividend % divisor).is_zero()
+}
+
+/// Specifications of every market with granularity rules.
+///
+/// Markets without a specification accept every order.
+#[derive(Debug, Clone, Default)]
+pub struct MarketSpecs {
+    specs: HashMap<Market, MarketSpec>,
+}
+
+impl MarketSpecs {
+    pub fn get(&self, market: &Market) -> Option<&MarketSpec> {
+        self.specs.get(market)
+    }
+
+    pub fn insert(&mut self, market: Market, spec: MarketSpec) {
+        self.specs.insert(market, spec);
+    }
+
+    /// Order-intake validation of the arguments of `createNewTask`, so clients can
+    /// catch orders that operators would reject before submitting them.
+    pub fn validate_new_order(
+        &self,
+        amount_owned: U256,
+        amount_not_owned: U256,
+        token_not_owned: Address,
+        token_owned: Address,
+        order_type: OrderType,
+    ) -> Result<(), Rejection> {
+        self.validate(&Order {
+            user: Address::ZERO,
+            amount_owned,
+            amount_not_owned,
+            token_not_owned,
+            token_owned,
+            slippage: U256::ZERO,
+            orderType: order_type.into(),
+            timestamp: U256::ZERO,
+            expiry: U256::ZERO,
+            triggerType: 0,
+            triggerPriceNumerator: U256::ZERO,
+            triggerPriceDenominator: U256::ZERO,
+            displayQuantity: U256::ZERO,
+            displayedAmount: U256::ZERO,
+            timestamp_matched: U256::ZERO,
+            isPartiallyFilled: false,
+            isFilled: false,
+            isCancelled: false,
+            isTriggered: false,
+        })
+    }
+
+    /// Checks an order against the specification of its market.
+    pub fn validate(&self, order: &Order) -> Result<(), Rejection> {
+        let Some(market) = Market::of(order) else {
+            return Ok(());
+        };
+        match self.specs.get(&market) {
+            Some(spec) => spec.validate(&market, order),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parses a comma separated list of
+/// `token_a:token_b:min_size:lot_size:tick_size:min_notional` entries, where the
+/// tick size is either an integer or a `numerator/denominator` ratio.
+impl FromStr for MarketSpecs {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut specs = MarketSpecs::default();
+
+        for entry in s
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let invalid = || Error::Config(format!("Invalid market specification: {}", entry));
+            let parts: Vec<&str> = entry.split(':').map(str::trim).collect();
+            let [token_a, token_b, min_size, lot_size, tick_size, min_notional] = parts[..] else {
+                return Err(invalid());
+            };
+
+            let market = Market::new(
+                token_a.parse().map_err(|_| invalid())?,
+                token_b.parse().map_err(|_| invalid())?,
+            )
+            .ok_or_else(invalid)?;
+            let amount = |value: &str| U256::from_str(value).map_err(|_| invalid());
+            let tick_size = match tick_size.split_once('/') {
+                Some((numerator, denominator)) => {
+                    Price::new(amount(numerator)?, amount(denominator)?).ok_or_else(invalid)?
+                }
+                None => Price::new(amount(tick_size)?, U256::from(1)).ok_or_else(invalid)?,
+            };
+
+            specs.insert(
+                market,
+                MarketSpec {
+                    min_size: amount(min_size)?,
+                    lot_size: amount(lot_size)?,
+                    tick_size: Some(tick_size),
+                    min_notional: amount(min_notional)?,
+                },
+            );
+        }
+
+        Ok(specs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::test_support::{order, DAI, USDC, WETH};
+
+    fn specs() -> MarketSpecs {
+        // Sizes of 10 WETH in lots of 5, prices in ticks of 1/2 USDC, at least 100 USDC
+        format!("{}:{}:10:5:1/2:100", USDC, WETH).parse().unwrap()
+    }
+
+    #[test]
+    fn test_valid_orders_are_accepted() {
+        let specs = specs();
+        assert_eq!(specs.validate(&order(0, WETH, 10, USDC, 100, 0)), Ok(()));
+        assert_eq!(specs.validate(&order(0, USDC, 105, WETH, 10, 0)), Ok(()));
+        assert_eq!(specs.validate(&order(0, WETH, 20, USDC, 250, 0)), Ok(()));
+    }
+
+    #[test]
+    fn test_invalid_orders_are_rejected() {
+        let specs = specs();
+        assert_eq!(
+            specs.validate(&order(0, WETH, 5, USDC, 100, 0)),
+            Err(Rejection::BelowMinSize)
+        );
+        assert_eq!(
+            specs.validate(&order(0, USDC, 120, WETH, 12, 0)),
+            Err(Rejection::InvalidLotSize)
+        );
+        assert_eq!(
+            specs.validate(&order(0, WETH, 10, USDC, 90, 0)),
+            Err(Rejection::BelowMinNotional)
+        );
+        // 101 / 10 = 10.1 USDC per WETH is not a multiple of 0.5
+        assert_eq!(
+            specs.validate(&order(0, WETH, 10, USDC, 101, 0)),
+            Err(Rejection::InvalidTickSize)
+        );
+    }
+
+    #[test]
+    fn test_intake_validation() {
+        let specs = specs();
+        assert_eq!(
+            specs.validate_new_order(
+                U256::from(10),
+                U256::from(100),
+                USDC,
+                WETH,
+                OrderType::Limit
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            specs.validate_new_order(
+                U256::from(10),
+                U256::from(101),
+                USDC,
+                WETH,
+                OrderType::Limit
+            ),
+            Err(Rejection::InvalidTickSize)
+        );
+    }
+
+    #[test]
+    fn test_market_orders_skip_tick_size() {
+        let mut market_order = order(0, WETH, 10, USDC, 101, 0);
+        market_order.orderType = OrderType::Market.into();
+        assert_eq!(specs().validate(&market_order), Ok(()));
+    }
+
+    #[test]
+    fn test_unknown_markets_accept_every_order() {
+        let other = order(0, WETH, 1, DAI, 1, 0);
+        assert_eq!(specs().validate(&other), Ok(()));
+        assert_eq!(
+            MarketSpecs::default().validate(&order(0, WETH, 1, USDC, 1, 0)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_specs_from_config() {
+        let specs = specs();
+        let spec = specs.get(&Market::new(WETH, USDC).unwrap()).unwrap();
+        assert_eq!(spec.min_size, U256::from(10));
+        assert_eq!(spec.tick_size, Price::new(U256::from(1), U256::from(2)));
+
+        let integer_tick: MarketSpecs = format!("{}:{}:0:1:5:0", WETH, USDC).parse().unwrap();
+        let spec = integer_tick.get(&Market::new(WETH, USDC).unwrap()).unwrap();
+        assert_eq!(spec.tick_size, Price::new(U256::from(5), U256::from(1)));
+
+        assert!(format!("{}:{}:10:5:1/2", WETH, USDC)
+            .parse::<MarketSpecs>()
+            .is_err());
+        assert!(format!("{}:{}:10:5:1/0:100", WETH, USDC)
+            .parse::<MarketSpecs>()
+            .is_err());
+        assert!(format!("{}:{}:10:5:1:100", WETH, WETH)
+            .parse::<MarketSpecs>()
+            .is_err());
+        assert!(""
+            .parse::<MarketSpecs>()
+            .unwrap()
+            .get(&Market::new(WETH, USDC).unwrap())
+            .is_none());
+    }
+}
@@ -0,0 +1,73 @@
+use crate::Error;
+use std::str::FromStr;
+
+/// What happens when an incoming order would match a resting order from the same user.
+///
+/// The discriminant is the code reported in `TaskResponse.selfTradeOutcome`, where
+/// zero means self-trade prevention was not triggered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SelfTradePrevention {
+    /// Cancel the incoming order, leaving the resting order untouched.
+    #[default]
+    CancelNewest = 1,
+    /// Cancel the resting order and keep matching the incoming order.
+    CancelOldest = 2,
+    /// Cancel both the incoming and the resting order.
+    CancelBoth = 3,
+    /// Reduce both orders by the smaller of the two and cancel whichever reaches zero.
+    DecrementAndCancel = 4,
+}
+
+impl SelfTradePrevention {
+    /// Code reported in the `TaskResponse` when this mode was applied.
+    pub fn outcome(applied: Option<Self>) -> u8 {
+        applied.map_or(0, |mode| mode as u8)
+    }
+}
+
+impl FromStr for SelfTradePrevention {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "cancel-newest" => Ok(SelfTradePrevention::CancelNewest),
+            "cancel-oldest" => Ok(SelfTradePrevention::CancelOldest),
+            "cancel-both" => Ok(SelfTradePrevention::CancelBoth),
+            "decrement-and-cancel" => Ok(SelfTradePrevention::DecrementAndCancel),
+            other => Err(Error::Config(format!(
+                "Unknown self-trade prevention mode: {}",
+                other
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mode_from_config() {
+        assert_eq!(
+            "cancel-newest".parse::<SelfTradePrevention>().unwrap(),
+            SelfTradePrevention::CancelNewest
+        );
+        assert_eq!(
+            "Decrement-And-Cancel"
+                .parse::<SelfTradePrevention>()
+                .unwrap(),
+            SelfTradePrevention::DecrementAndCancel
+        );
+        assert!("skip".parse::<SelfTradePrevention>().is_err());
+    }
+
+    #[test]
+    fn test_outcome_codes() {
+        assert_eq!(SelfTradePrevention::outcome(None), 0);
+        assert_eq!(
+            SelfTradePrevention::outcome(Some(SelfTradePrevention::CancelBoth)),
+            3
+        );
+    }
+}
@@ -0,0 +1,450 @@
+use crate::matching::error::{add, sub, MatchError};
+use crate::matching::price::{narrow, Price};
+use crate::Error;
+use alloy_primitives::{U256, U512};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// A resting order that crosses the incoming order and can be filled against it.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    /// Index of the resting order in the orderbook.
+    pub index: usize,
+    /// The resting order's price, quoted in the same units as the incoming order.
+    pub price: Price,
+    /// Time the resting order was placed.
+    pub timestamp: U256,
+    /// Amount of the resting order still available to fill.
+    pub available: U256,
+}
+
+/// The part of the incoming order allocated to a single resting order.
+#[derive(Debug, Clone)]
+pub struct Allocation {
+    /// Index of the resting order in the orderbook.
+    pub index: usize,
+    /// Amount filled against the resting order.
+    pub amount: U256,
+    /// Price the fill executes at, quoted in the same units as the incoming order.
+    pub price: Price,
+}
+
+/// Decides how an incoming order is allocated across the resting orders it crosses.
+///
+/// Implementations must be deterministic: every operator has to reach the same
+/// allocation, and therefore the same `TaskResponse` digest, from the same inputs.
+pub trait MatchingStrategy: fmt::Debug + Send + Sync {
+    /// Name of the strategy as used in configuration.
+    fn name(&self) -> &'static str;
+
+    /// Allocates up to `amount` of the incoming order, whose limit price is `limit`,
+    /// across at most `max_fills` of the `candidates`.
+    ///
+    /// `candidates` are sorted in price-time priority and all cross `limit`.
+    fn allocate(
+        &self,
+        limit: Price,
+        amount: U256,
+        candidates: &[Candidate],
+        max_fills: usize,
+    ) -> Result<Vec<Allocation>, MatchError>;
+}
+
+/// Price-time priority: the best priced, then oldest, resting order is filled first.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fifo;
+
+impl MatchingStrategy for Fifo {
+    fn name(&self) -> &'static str {
+        "fifo"
+    }
+
+    fn allocate(
+        &self,
+        _limit: Price,
+        amount: U256,
+        candidates: &[Candidate],
+        max_fills: usize,
+    ) -> Result<Vec<Allocation>, MatchError> {
+        sweep(amount, candidates, max_fills, |candidate| candidate.price)
+    }
+}
+
+/// Pro-rata: every resting order at the best price level receives a share of the
+/// incoming order proportional to its available size.
+///
+/// Rounding remainders are handed out one unit at a time in time priority. Once a
+/// level is exhausted the next best level is allocated the same way.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProRata;
+
+impl MatchingStrategy for ProRata {
+    fn name(&self) -> &'static str {
+        "pro-rata"
+    }
+
+    fn allocate(
+        &self,
+        _limit: Price,
+        amount: U256,
+        candidates: &[Candidate],
+        max_fills: usize,
+    ) -> Result<Vec<Allocation>, MatchError> {
+        let mut allocations = Vec::new();
+        let mut remaining = amount;
+
+        for level in candidates.chunk_by(|a, b| a.price == b.price) {
+            if remaining.is_zero() || allocations.len() >= max_fills {
+                break;
+            }
+
+            let level: Vec<&Candidate> = level
+                .iter()
+                .filter(|candidate| !candidate.available.is_zero())
+                .take(max_fills.saturating_sub(allocations.len()))
+                .collect();
+            let total = level.iter().try_fold(U256::ZERO, |total, candidate| {
+                add(total, candidate.available, "pro-rata level size")
+            })?;
+
+            if total <= remaining {
+                for candidate in level {
+                    allocations.push(Allocation {
+                        index: candidate.index,
+                        amount: candidate.available,
+                        price: candidate.price,
+                    });
+                }
+                remaining = sub(remaining, total, "pro-rata remainder")?;
+                continue;
+            }
+
+            // `total` exceeds `remaining`, so it is never zero and every share fits
+            let mut amounts = level
+                .iter()
+                .map(|candidate| {
+                    let share =
+                        U512::from(remaining) * U512::from(candidate.available) / U512::from(total);
+                    narrow(share).ok_or(MatchError::Overflow("pro-rata share"))
+                })
+                .collect::<Result<Vec<U256>, _>>()?;
+            let allocated = amounts
+                .iter()
+                .try_fold(U256::ZERO, |sum, a| add(sum, *a, "pro-rata shares"))?;
+            let mut leftover = sub(remaining, allocated, "pro-rata leftover")?;
+            for (amount, candidate) in amounts.iter_mut().zip(&level) {
+                if leftover.is_zero() {
+                    break;
+                }
+                if *amount < candidate.available {
+                    *amount = add(*amount, U256::from(1), "pro-rata leftover")?;
+                    leftover = sub(leftover, U256::from(1), "pro-rata leftover")?;
+                }
+            }
+
+            for (amount, candidate) in amounts.into_iter().zip(level) {
+                if !amount.is_zero() {
+                    allocations.push(Allocation {
+                        index: candidate.index,
+                        amount,
+                        price: candidate.price,
+                    });
+                }
+            }
+            break;
+        }
+
+        Ok(allocations)
+    }
+}
+
+/// Midpoint cross: resting orders are filled in price-time priority, but each fill
+/// executes at the midpoint between the incoming order's limit and the resting price.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Midpoint;
+
+impl MatchingStrategy for Midpoint {
+    fn name(&self) -> &'static str {
+        "midpoint"
+    }
+
+    fn allocate(
+        &self,
+        limit: Price,
+        amount: U256,
+        candidates: &[Candidate],
+        max_fills: usize,
+    ) -> Result<Vec<Allocation>, MatchError> {
+        sweep(amount, candidates, max_fills, |candidate| {
+            limit.midpoint(candidate.price).unwrap_or(candidate.price)
+        })
+    }
+}
+
+/// Fills candidates in the order given until `amount` or `max_fills` is exhausted.
+fn sweep(
+    amount: U256,
+    candidates: &[Candidate],
+    max_fills: usize,
+    price: impl Fn(&Candidate) -> Price,
+) -> Result<Vec<Allocation>, MatchError> {
+    let mut allocations = Vec::new();
+    let mut remaining = amount;
+
+    for candidate in candidates {
+        if remaining.is_zero() || allocations.len() >= max_fills {
+            break;
+        }
+
+        let fill_amount = remaining.min(candidate.available);
+        if fill_amount.is_zero() {
+            continue;
+        }
+        remaining = sub(remaining, fill_amount, "sweep remainder")?;
+
+        allocations.push(Allocation {
+            index: candidate.index,
+            amount: fill_amount,
+            price: price(candidate),
+        });
+    }
+
+    Ok(allocations)
+}
+
+/// The matching strategies that can be selected through configuration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StrategyKind {
+    #[default]
+    Fifo,
+    ProRata,
+    Midpoint,
+}
+
+impl StrategyKind {
+    pub fn build(self) -> Arc<dyn MatchingStrategy> {
+        match self {
+            StrategyKind::Fifo => Arc::new(Fifo),
+            StrategyKind::ProRata => Arc::new(ProRata),
+            StrategyKind::Midpoint => Arc::new(Midpoint),
+        }
+    }
+}
+
+impl FromStr for StrategyKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "fifo" | "price-time" => Ok(StrategyKind::Fifo),
+            "pro-rata" | "prorata" => Ok(StrategyKind::ProRata),
+            "midpoint" => Ok(StrategyKind::Midpoint),
+            other => Err(Error::Config(format!(
+                "Unknown matching strategy: {}",
+                other
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STRATEGIES: [StrategyKind; 3] = [
+        StrategyKind::Fifo,
+        StrategyKind::ProRata,
+        StrategyKind::Midpoint,
+    ];
+
+    fn price(numerator: u64, denominator: u64) -> Price {
+        Price::new(U256::from(numerator), U256::from(denominator)).unwrap()
+    }
+
+    fn candidate(index: usize, price: Price, timestamp: u64, available: u64) -> Candidate {
+        Candidate {
+            index,
+            price,
+            timestamp: U256::from(timestamp),
+            available: U256::from(available),
+        }
+    }
+
+    /// Two resting orders at the best level and one at a worse level.
+    fn book() -> Vec<Candidate> {
+        vec![
+            candidate(3, price(1, 2), 10, 30),
+            candidate(1, price(1, 2), 20, 10),
+            candidate(0, price(3, 4), 5, 50),
+        ]
+    }
+
+    fn total(allocations: &[Allocation]) -> U256 {
+        allocations.iter().fold(U256::ZERO, |sum, a| sum + a.amount)
+    }
+
+    fn amounts(allocations: &[Allocation]) -> Vec<(usize, u64)> {
+        allocations
+            .iter()
+            .map(|a| (a.index, a.amount.to::<u64>()))
+            .collect()
+    }
+
+    /// Properties every strategy must satisfy.
+    fn check_conformance(strategy: &dyn MatchingStrategy) {
+        let limit = price(1, 1);
+        let candidates = book();
+
+        for amount in [0u64, 1, 7, 25, 40, 41, 90, 1000] {
+            for max_fills in [0usize, 1, 2, 16] {
+                let amount = U256::from(amount);
+                let allocations = strategy
+                    .allocate(limit, amount, &candidates, max_fills)
+                    .unwrap();
+
+                assert!(
+                    allocations.len() <= max_fills,
+                    "{}: too many fills",
+                    strategy.name()
+                );
+                assert!(
+                    total(&allocations) <= amount,
+                    "{}: over-allocated",
+                    strategy.name()
+                );
+
+                for allocation in &allocations {
+                    let candidate = candidates
+                        .iter()
+                        .find(|c| c.index == allocation.index)
+                        .expect("allocated to an unknown order");
+                    assert!(
+                        !allocation.amount.is_zero(),
+                        "{}: empty fill",
+                        strategy.name()
+                    );
+                    assert!(allocation.amount <= candidate.available);
+                    assert!(
+                        allocation.price <= limit,
+                        "{}: worse than limit",
+                        strategy.name()
+                    );
+                    assert!(allocation.price >= candidate.price);
+                }
+
+                let mut indices: Vec<usize> = allocations.iter().map(|a| a.index).collect();
+                indices.sort();
+                indices.dedup();
+                assert_eq!(
+                    indices.len(),
+                    allocations.len(),
+                    "{}: duplicate fill",
+                    strategy.name()
+                );
+
+                // Nothing trades at a worse level until the best level is exhausted
+                if allocations.iter().any(|a| a.index == 0) {
+                    assert!(amounts(&allocations).contains(&(3, 30)));
+                    assert!(amounts(&allocations).contains(&(1, 10)));
+                }
+
+                // Fully filled whenever there is enough liquidity and fill slots
+                if max_fills >= candidates.len() && amount <= U256::from(90) {
+                    assert_eq!(
+                        total(&allocations),
+                        amount,
+                        "{}: under-filled",
+                        strategy.name()
+                    );
+                }
+
+                let again = strategy
+                    .allocate(limit, amount, &candidates, max_fills)
+                    .unwrap();
+                assert_eq!(
+                    amounts(&allocations),
+                    amounts(&again),
+                    "{}: not deterministic",
+                    strategy.name()
+                );
+            }
+        }
+
+        assert!(strategy
+            .allocate(limit, U256::from(10), &[], 16)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_conformance() {
+        for kind in STRATEGIES {
+            check_conformance(kind.build().as_ref());
+        }
+    }
+
+    #[test]
+    fn test_fifo_fills_in_priority_order() {
+        let allocations = Fifo
+            .allocate(price(1, 1), U256::from(35), &book(), 16)
+            .unwrap();
+        assert_eq!(amounts(&allocations), vec![(3, 30), (1, 5)]);
+        assert_eq!(allocations[0].price, price(1, 2));
+    }
+
+    #[test]
+    fn test_pro_rata_splits_best_level() {
+        let allocations = ProRata
+            .allocate(price(1, 1), U256::from(20), &book(), 16)
+            .unwrap();
+        assert_eq!(amounts(&allocations), vec![(3, 15), (1, 5)]);
+    }
+
+    #[test]
+    fn test_pro_rata_hands_out_remainder_in_time_priority() {
+        let candidates = vec![
+            candidate(0, price(1, 1), 1, 10),
+            candidate(1, price(1, 1), 2, 10),
+            candidate(2, price(1, 1), 3, 10),
+        ];
+        let allocations = ProRata
+            .allocate(price(1, 1), U256::from(5), &candidates, 16)
+            .unwrap();
+        assert_eq!(amounts(&allocations), vec![(0, 2), (1, 2), (2, 1)]);
+    }
+
+    #[test]
+    fn test_pro_rata_moves_to_next_level() {
+        let allocations = ProRata
+            .allocate(price(1, 1), U256::from(45), &book(), 16)
+            .unwrap();
+        assert_eq!(amounts(&allocations), vec![(3, 30), (1, 10), (0, 5)]);
+    }
+
+    #[test]
+    fn test_midpoint_executes_between_limit_and_resting_price() {
+        let allocations = Midpoint
+            .allocate(price(1, 1), U256::from(10), &book(), 16)
+            .unwrap();
+        assert_eq!(amounts(&allocations), vec![(3, 10)]);
+        assert_eq!(allocations[0].price, price(3, 4));
+    }
+
+    #[test]
+    fn test_strategy_from_config() {
+        assert_eq!("fifo".parse::<StrategyKind>().unwrap(), StrategyKind::Fifo);
+        assert_eq!(
+            "Pro-Rata".parse::<StrategyKind>().unwrap(),
+            StrategyKind::ProRata
+        );
+        assert_eq!(
+            "midpoint".parse::<StrategyKind>().unwrap(),
+            StrategyKind::Midpoint
+        );
+        assert!("random".parse::<StrategyKind>().is_err());
+        for kind in STRATEGIES {
+            assert_eq!(kind.build().name().parse::<StrategyKind>().unwrap(), kind);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use crate::matching::engine::MatchingConfig;
+use crate::matching::fees::FeeSchedule;
+use crate::matching::order_type::OrderType;
+use crate::matching::stp::SelfTradePrevention;
+use crate::matching::strategy::Fifo;
+use crate::IOrderBookTaskManager::Order;
+use alloy_primitives::{Address, U256};
+use std::sync::Arc;
+
+// WETH sorts before USDC, so WETH is the base token of the WETH/USDC market
+pub const WETH: Address = Address::repeat_byte(0xAA);
+pub const USDC: Address = Address::repeat_byte(0xBB);
+pub const DAI: Address = Address::repeat_byte(0xCC);
+pub const WBTC: Address = Address::repeat_byte(0xDD);
+
+/// A live limit order of `user` giving `amount_owned` of `token_owned` for
+/// `amount_not_owned` of `token_not_owned`, accepting `slippage` percent.
+///
+/// Every other field is zero: no timestamp, expiry, trigger or display quantity.
+/// Tests override what they need with struct update syntax.
+pub fn order(
+    user: u8,
+    token_owned: Address,
+    amount_owned: u64,
+    token_not_owned: Address,
+    amount_not_owned: u64,
+    slippage: u64,
+) -> Order {
+    Order {
+        user: Address::repeat_byte(user),
+        amount_owned: U256::from(amount_owned),
+        amount_not_owned: U256::from(amount_not_owned),
+        token_not_owned,
+        token_owned,
+        slippage: U256::from(slippage),
+        orderType: OrderType::Limit.into(),
+        timestamp: U256::ZERO,
+        expiry: U256::ZERO,
+        triggerType: 0,
+        triggerPriceNumerator: U256::ZERO,
+        triggerPriceDenominator: U256::ZERO,
+        displayQuantity: U256::ZERO,
+        displayedAmount: U256::ZERO,
+        timestamp_matched: U256::ZERO,
+        isPartiallyFilled: false,
+        isFilled: false,
+        isCancelled: false,
+        isTriggered: false,
+    }
+}
+
+/// Continuous FIFO matching with no fees, market specs or batch markets.
+pub fn config() -> MatchingConfig {
+    MatchingConfig {
+        strategy: Arc::new(Fifo),
+        max_fills: 16,
+        self_trade_prevention: SelfTradePrevention::CancelNewest,
+        fees: FeeSchedule::default(),
+        markets: Arc::default(),
+        batch_markets: Arc::default(),
+        batch_window_blocks: 1,
+        max_ring_length: 3,
+    }
+}
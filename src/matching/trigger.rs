@@ -0,0 +1,359 @@
+use crate::matching::market::{Market, Side};
+use crate::matching::price::Price;
+use crate::IOrderBookTaskManager::{Order, Task, TaskResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// When a conditional order wakes up, as encoded in `Order.triggerType`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum TriggerType {
+    /// A regular order, live as soon as it is created.
+    #[default]
+    None = 0,
+    /// Fires when the market moves against the order's side: a bid once the last price
+    /// rises to the trigger, an ask once it falls to it.
+    Stop = 1,
+    /// Fires when the market moves in favour of the order's side: a bid once the last
+    /// price falls to the trigger, an ask once it rises to it.
+    TakeProfit = 2,
+}
+
+impl TriggerType {
+    /// The trigger of an order, or `None` if the order carries an unknown trigger type.
+    pub fn of(order: &Order) -> Option<Self> {
+        Self::try_from(order.triggerType).ok()
+    }
+}
+
+impl TryFrom<u8> for TriggerType {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(TriggerType::None),
+            1 => Ok(TriggerType::Stop),
+            2 => Ok(TriggerType::TakeProfit),
+            other => Err(other),
+        }
+    }
+}
+
+impl From<TriggerType> for u8 {
+    fn from(value: TriggerType) -> Self {
+        value as u8
+    }
+}
+
+/// Returns true if the order carries a trigger that has not fired yet.
+///
+/// Dormant orders sit in the book but are never matched, neither as the incoming
+/// order of their own task nor as resting liquidity. Once the trigger fires the
+/// contract replaces them with a live copy, see `triggerOrders`.
+pub fn is_dormant(order: &Order) -> bool {
+    order.triggerType != u8::from(TriggerType::None) && !order.isTriggered
+}
+
+/// The trigger price of an order in token1 per token0, or `None` if it has no trigger.
+pub fn trigger_price(order: &Order) -> Option<Price> {
+    Price::new(order.triggerPriceNumerator, order.triggerPriceDenominator)
+}
+
+/// Returns true if a dormant order's trigger is crossed by `last`, the last traded
+/// price of its market in token1 per token0.
+pub fn fires(order: &Order, last: Price) -> bool {
+    if !is_dormant(order) || order.isFilled || order.isCancelled {
+        return false;
+    }
+    let (Some(market), Some(trigger), Some(trigger_type)) = (
+        Market::of(order),
+        trigger_price(order),
+        TriggerType::of(order),
+    ) else {
+        return false;
+    };
+    let Some(side) = market.side(order) else {
+        return false;
+    };
+
+    match (trigger_type, side) {
+        (TriggerType::Stop, Side::Bid) | (TriggerType::TakeProfit, Side::Ask) => last >= trigger,
+        (TriggerType::Stop, Side::Ask) | (TriggerType::TakeProfit, Side::Bid) => last <= trigger,
+        (TriggerType::None, _) => false,
+    }
+}
+
+/// Prices a response traded at, in token1 per token0 and in execution order.
+///
+/// Each fill and ring hop traded at the price implied by what the resting order gave
+/// and received, read from `book`, the orders held by the contract at the task's block
+/// that the response was matched against. A batch auction traded at its uniform
+/// clearing price. Fills that cannot be priced from the book are skipped.
+pub fn traded_prices(task: &Task, book: &[Order], response: &TaskResponse) -> Vec<(Market, Price)> {
+    if response.isBatch {
+        return Market::of(&task.order)
+            .zip(Price::new(
+                response.clearingPriceNumerator,
+                response.clearingPriceDenominator,
+            ))
+            .into_iter()
+            .collect();
+    }
+
+    response
+        .fills
+        .iter()
+        .chain(response.ringUpdates.iter())
+        .filter_map(|fill| {
+            let index = usize::try_from(fill.orderIndex).ok()?;
+            let resting = book.get(index)?;
+            let given = resting
+                .amount_owned
+                .checked_sub(fill.newOrder.amount_owned)?;
+            let received = resting
+                .amount_not_owned
+                .checked_sub(fill.newOrder.amount_not_owned)?;
+            let market = Market::of(resting)?;
+            let price = match market.side(resting)? {
+                Side::Bid => Price::new(given, received)?,
+                Side::Ask => Price::new(received, given)?,
+            };
+            Some((market, price))
+        })
+        .collect()
+}
+
+/// Dormant orders waiting for their trigger, and the last traded price of each market.
+#[derive(Debug, Clone, Default)]
+pub struct TriggerBook {
+    dormant: BTreeMap<usize, Order>,
+    last_prices: HashMap<Market, Price>,
+}
+
+impl TriggerBook {
+    /// Tracks the order at `index` if it is dormant and still live. Returns false otherwise.
+    pub fn insert(&mut self, index: usize, order: &Order) -> bool {
+        if !is_dormant(order) || order.isFilled || order.isCancelled || Market::of(order).is_none()
+        {
+            return false;
+        }
+        self.dormant.insert(index, order.clone());
+        true
+    }
+
+    /// Tracks every dormant order of `orders`, the contract's orders at their own index.
+    /// Returns how many are tracked.
+    pub fn insert_all(&mut self, orders: &[Order]) -> usize {
+        orders
+            .iter()
+            .enumerate()
+            .filter(|(index, order)| self.insert(*index, order))
+            .count()
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Order> {
+        self.dormant.remove(&index)
+    }
+
+    pub fn last_price(&self, market: &Market) -> Option<Price> {
+        self.last_prices.get(market).copied()
+    }
+
+    /// Records a trade in `market` at `price` and returns the indices of the dormant
+    /// orders it fired, lowest first. Fired orders are no longer tracked.
+    pub fn update(&mut self, market: Market, price: Price) -> Vec<usize> {
+        self.last_prices.insert(market, price);
+
+        let fired: Vec<usize> = self
+            .dormant
+            .iter()
+            .filter(|(_, order)| Market::of(order) == Some(market) && fires(order, price))
+            .map(|(index, _)| *index)
+            .collect();
+        for index in &fired {
+            self.dormant.remove(index);
+        }
+        fired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::test_support::{order, DAI, USDC, WETH};
+    use crate::IOrderBookTaskManager::Fill;
+    use alloy_primitives::U256;
+
+    /// Makes the order dormant until the last price reaches `trigger`.
+    fn with_trigger(mut order: Order, trigger_type: TriggerType, trigger: u64) -> Order {
+        order.triggerType = trigger_type.into();
+        order.triggerPriceNumerator = U256::from(trigger);
+        order.triggerPriceDenominator = U256::from(1);
+        order
+    }
+
+    fn price(value: u64) -> Price {
+        Price::new(U256::from(value), U256::from(1)).unwrap()
+    }
+
+    #[test]
+    fn test_round_trips_through_u8() {
+        for trigger_type in [
+            TriggerType::None,
+            TriggerType::Stop,
+            TriggerType::TakeProfit,
+        ] {
+            assert_eq!(
+                TriggerType::try_from(u8::from(trigger_type)),
+                Ok(trigger_type)
+            );
+        }
+        assert_eq!(TriggerType::try_from(3), Err(3));
+    }
+
+    #[test]
+    fn test_fires_in_the_direction_of_the_trigger() {
+        // WETH is token0, so USDC owners bid and WETH owners ask
+        let bid = order(1, USDC, 100, WETH, 100, 5);
+        let ask = order(1, WETH, 100, USDC, 100, 5);
+
+        let stop_buy = with_trigger(bid.clone(), TriggerType::Stop, 10);
+        assert!(!fires(&stop_buy, price(9)));
+        assert!(fires(&stop_buy, price(10)));
+
+        let stop_sell = with_trigger(ask.clone(), TriggerType::Stop, 10);
+        assert!(!fires(&stop_sell, price(11)));
+        assert!(fires(&stop_sell, price(10)));
+
+        let take_profit_buy = with_trigger(bid, TriggerType::TakeProfit, 10);
+        assert!(fires(&take_profit_buy, price(9)));
+        assert!(!fires(&take_profit_buy, price(11)));
+
+        let take_profit_sell = with_trigger(ask, TriggerType::TakeProfit, 10);
+        assert!(fires(&take_profit_sell, price(11)));
+        assert!(!fires(&take_profit_sell, price(9)));
+    }
+
+    #[test]
+    fn test_only_dormant_orders_fire() {
+        let bid = order(1, USDC, 100, WETH, 100, 5);
+        let plain = with_trigger(bid.clone(), TriggerType::None, 10);
+        assert!(!is_dormant(&plain));
+        assert!(!fires(&plain, price(10)));
+
+        let mut triggered = with_trigger(bid.clone(), TriggerType::Stop, 10);
+        triggered.isTriggered = true;
+        assert!(!is_dormant(&triggered));
+        assert!(!fires(&triggered, price(10)));
+
+        let mut cancelled = with_trigger(bid, TriggerType::Stop, 10);
+        cancelled.isCancelled = true;
+        assert!(!fires(&cancelled, price(10)));
+    }
+
+    #[test]
+    fn test_trigger_book_fires_each_order_once() {
+        let bid = order(1, USDC, 100, WETH, 100, 5);
+        let ask = order(1, WETH, 100, USDC, 100, 5);
+
+        let mut book = TriggerBook::default();
+        assert!(!book.insert(0, &bid));
+        assert!(book.insert(1, &with_trigger(bid.clone(), TriggerType::Stop, 10)));
+        assert!(book.insert(2, &with_trigger(ask, TriggerType::Stop, 8)));
+        assert!(book.insert(3, &with_trigger(bid.clone(), TriggerType::Stop, 12)));
+
+        let market = Market::new(WETH, USDC).unwrap();
+        assert!(book.update(market, price(9)).is_empty());
+        assert_eq!(book.last_price(&market), Some(price(9)));
+
+        assert_eq!(book.update(market, price(12)), vec![1, 3]);
+        assert!(book.update(market, price(12)).is_empty());
+        assert_eq!(book.update(market, price(7)), vec![2]);
+
+        // Trades in other markets never fire an order
+        book.insert(4, &with_trigger(bid, TriggerType::Stop, 1));
+        let other = Market::new(WETH, DAI).unwrap();
+        assert!(book.update(other, price(100)).is_empty());
+        assert!(book.remove(4).is_some());
+    }
+
+    #[test]
+    fn test_trigger_book_tracks_the_dormant_orders_of_the_contract() {
+        let bid = order(1, USDC, 100, WETH, 100, 5);
+        let stop = with_trigger(bid.clone(), TriggerType::Stop, 10);
+        let mut retired = stop.clone();
+        retired.isTriggered = true;
+        retired.isCancelled = true;
+        let mut cancelled = stop.clone();
+        cancelled.isCancelled = true;
+
+        let mut book = TriggerBook::default();
+        assert_eq!(
+            book.insert_all(&[bid, retired, stop.clone(), cancelled, stop]),
+            2
+        );
+        let market = Market::new(WETH, USDC).unwrap();
+        assert_eq!(book.update(market, price(10)), vec![2, 4]);
+    }
+
+    #[test]
+    fn test_traded_prices_from_fills() {
+        // A resting ask of 10 WETH for 200 USDC fills half its size
+        let resting = Order {
+            amount_owned: U256::from(10),
+            amount_not_owned: U256::from(200),
+            ..order(1, WETH, 100, USDC, 100, 5)
+        };
+        let filled = Order {
+            amount_owned: U256::from(5),
+            amount_not_owned: U256::from(100),
+            ..resting.clone()
+        };
+        // The snapshot in the task is not used, the order may have changed since
+        let task = Task {
+            order: order(1, USDC, 100, WETH, 100, 5),
+            orderbook: Vec::new(),
+            taskCreatedBlock: 0,
+            quorumNumbers: Default::default(),
+            quorumThresholdPercentage: 0,
+        };
+        let response = TaskResponse {
+            referenceTaskIndex: 1,
+            outcome: 1,
+            rejectionReason: 0,
+            newOrder: task.order.clone(),
+            fills: vec![Fill {
+                orderIndex: U256::ZERO,
+                newOrder: filled,
+                makerFee: U256::ZERO,
+            }],
+            takerFee: U256::ZERO,
+            selfTradeOutcome: 0,
+            selfTradeUpdates: Vec::new(),
+            ringUpdates: Vec::new(),
+            isBatch: false,
+            clearingPriceNumerator: U256::ZERO,
+            clearingPriceDenominator: U256::ZERO,
+            batchTasks: Vec::new(),
+        };
+
+        let market = Market::new(WETH, USDC).unwrap();
+        let book = vec![resting];
+        assert_eq!(
+            traded_prices(&task, &book, &response),
+            vec![(market, price(20))]
+        );
+
+        let batch = TaskResponse {
+            fills: Vec::new(),
+            isBatch: true,
+            clearingPriceNumerator: U256::from(21),
+            clearingPriceDenominator: U256::from(1),
+            ..response
+        };
+        assert_eq!(
+            traded_prices(&task, &book, &batch),
+            vec![(market, price(21))]
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use alloy_primitives::U256;
+use blueprint_sdk::eigensdk::crypto_bls::{BlsG2Point, OperatorId};
+use blueprint_sdk::eigensdk::types::avs::TaskResponseDigest;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Why a signature was not counted towards a task's quorum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum SignatureError {
+    #[error("operator {0} is not registered in the task's quorums")]
+    UnknownOperator(OperatorId),
+    #[error("signature of operator {0} does not match its registered public key")]
+    InvalidSignature(OperatorId),
+    #[error("operator {0} already signed a response to the task")]
+    DuplicateOperator(OperatorId),
+}
+
+/// Where a task stands after a signature has been recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    /// The signed response is still short of the threshold in some quorum.
+    Pending,
+    /// This signature brought its response to the threshold in every quorum.
+    Reached,
+    /// Another signature already reached the threshold, this one is not needed.
+    Complete,
+}
+
+/// Signatures collected for one task, keyed by the response digest and the operator
+/// that signed it.
+///
+/// Operators may sign different responses, so the stake is counted per digest and a
+/// response reaches its quorum once its signers hold `threshold_percentage` of the
+/// stake of every quorum of the task. Each operator is counted once per task.
+///
+/// The G2 public keys the operators registered are kept along with their stake, so
+/// signatures are verified without reading the registry again.
+#[derive(Debug, Clone, Default)]
+pub struct TaskSignatures {
+    threshold_percentage: u32,
+    total_stakes: Vec<U256>,
+    operator_stakes: HashMap<OperatorId, Vec<U256>>,
+    public_keys: HashMap<OperatorId, BlsG2Point>,
+    signers: HashMap<OperatorId, TaskResponseDigest>,
+    signed_stakes: HashMap<TaskResponseDigest, Vec<U256>>,
+    reached: Option<TaskResponseDigest>,
+}
+
+impl TaskSignatures {
+    /// `operators` lists the operators and their stake in each quorum of the task, in
+    /// the order of the task's quorum numbers, as read at `taskCreatedBlock`.
+    pub fn new(threshold_percentage: u32, operators: Vec<Vec<(OperatorId, U256)>>) -> Self {
+        let quorums = operators.len();
+        let mut total_stakes = vec![U256::ZERO; quorums];
+        let mut operator_stakes: HashMap<OperatorId, Vec<U256>> = HashMap::new();
+        for (quorum, members) in operators.into_iter().enumerate() {
+            for (operator_id, stake) in members {
+                total_stakes[quorum] = total_stakes[quorum].saturating_add(stake);
+                operator_stakes
+                    .entry(operator_id)
+                    .or_insert_with(|| vec![U256::ZERO; quorums])[quorum] = stake;
+            }
+        }
+
+        Self {
+            threshold_percentage,
+            total_stakes,
+            operator_stakes,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the G2 public keys of the task's operators, as read at `taskCreatedBlock`.
+    pub fn with_public_keys(mut self, public_keys: HashMap<OperatorId, BlsG2Point>) -> Self {
+        self.public_keys = public_keys;
+        self
+    }
+
+    /// The G2 public key `operator_id` registered, if it is an operator of the task.
+    pub fn public_key(&self, operator_id: &OperatorId) -> Option<&BlsG2Point> {
+        self.public_keys.get(operator_id)
+    }
+
+    /// Checks that a signature of `operator_id` would be counted, without counting it.
+    pub fn check(&self, operator_id: OperatorId) -> Result<(), SignatureError> {
+        if !self.operator_stakes.contains_key(&operator_id) {
+            return Err(SignatureError::UnknownOperator(operator_id));
+        }
+        if self.signers.contains_key(&operator_id) {
+            return Err(SignatureError::DuplicateOperator(operator_id));
+        }
+        Ok(())
+    }
+
+    /// Counts the signature of `operator_id` over the response with `digest`.
+    pub fn record(
+        &mut self,
+        digest: TaskResponseDigest,
+        operator_id: OperatorId,
+    ) -> Result<Progress, SignatureError> {
+        self.check(operator_id)?;
+        let stakes = &self.operator_stakes[&operator_id];
+        self.signers.insert(operator_id, digest);
+
+        if self.reached.is_some() {
+            return Ok(Progress::Complete);
+        }
+
+        let signed = self
+            .signed_stakes
+            .entry(digest)
+            .or_insert_with(|| vec![U256::ZERO; stakes.len()]);
+        for (total, stake) in signed.iter_mut().zip(stakes) {
+            *total = total.saturating_add(*stake);
+        }
+
+        let threshold = U256::from(self.threshold_percentage);
+        let reached = self
+            .total_stakes
+            .iter()
+            .zip(signed.iter())
+            .all(|(total, signed)| {
+                !total.is_zero()
+                    && signed.saturating_mul(U256::from(100)) >= total.saturating_mul(threshold)
+            });
+        if !reached {
+            return Ok(Progress::Pending);
+        }
+        self.reached = Some(digest);
+        Ok(Progress::Reached)
+    }
+
+    /// The digest of the response that reached the quorum, if any.
+    pub fn reached(&self) -> Option<TaskResponseDigest> {
+        self.reached
+    }
+
+    /// The stake that signed the response with `digest`, per quorum of the task.
+    pub fn signed_stake(&self, digest: &TaskResponseDigest) -> Option<&[U256]> {
+        self.signed_stakes.get(digest).map(Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::B256;
+
+    fn operator(id: u8) -> OperatorId {
+        B256::repeat_byte(id)
+    }
+
+    fn stakes(members: &[(u8, u64)]) -> Vec<(OperatorId, U256)> {
+        members
+            .iter()
+            .map(|(id, stake)| (operator(*id), U256::from(*stake)))
+            .collect()
+    }
+
+    #[test]
+    fn test_quorum_is_reached_per_digest() {
+        let mut signatures =
+            TaskSignatures::new(67, vec![stakes(&[(1, 40), (2, 20), (3, 30), (4, 10)])]);
+        let agreed = B256::repeat_byte(0xAA);
+        let other = B256::repeat_byte(0xBB);
+
+        assert_eq!(
+            signatures.record(agreed, operator(1)),
+            Ok(Progress::Pending)
+        );
+        // A different response does not count towards the agreed one
+        assert_eq!(signatures.record(other, operator(2)), Ok(Progress::Pending));
+        assert_eq!(
+            signatures.signed_stake(&agreed),
+            Some(&[U256::from(40)][..])
+        );
+        assert_eq!(signatures.reached(), None);
+
+        assert_eq!(
+            signatures.record(agreed, operator(3)),
+            Ok(Progress::Reached)
+        );
+        assert_eq!(
+            signatures.signed_stake(&agreed),
+            Some(&[U256::from(70)][..])
+        );
+        assert_eq!(signatures.reached(), Some(agreed));
+        assert_eq!(
+            signatures.record(agreed, operator(4)),
+            Ok(Progress::Complete)
+        );
+    }
+
+    #[test]
+    fn test_every_quorum_must_reach_the_threshold() {
+        let mut signatures = TaskSignatures::new(
+            50,
+            vec![stakes(&[(1, 60), (2, 40)]), stakes(&[(1, 10), (2, 90)])],
+        );
+        let digest = B256::repeat_byte(0xAA);
+
+        assert_eq!(
+            signatures.record(digest, operator(1)),
+            Ok(Progress::Pending)
+        );
+        assert_eq!(
+            signatures.record(digest, operator(2)),
+            Ok(Progress::Reached)
+        );
+    }
+
+    #[test]
+    fn test_rejects_unknown_and_duplicate_operators() {
+        let mut signatures = TaskSignatures::new(100, vec![stakes(&[(1, 40), (2, 60)])]);
+        let digest = B256::repeat_byte(0xAA);
+
+        assert_eq!(
+            signatures.record(digest, operator(9)),
+            Err(SignatureError::UnknownOperator(operator(9)))
+        );
+        assert_eq!(signatures.check(operator(1)), Ok(()));
+        // Checking does not count the signature
+        assert_eq!(signatures.signed_stake(&digest), None);
+        assert_eq!(
+            signatures.record(digest, operator(1)),
+            Ok(Progress::Pending)
+        );
+        assert_eq!(
+            signatures.record(digest, operator(1)),
+            Err(SignatureError::DuplicateOperator(operator(1)))
+        );
+        assert_eq!(
+            signatures.check(operator(1)),
+            Err(SignatureError::DuplicateOperator(operator(1)))
+        );
+        // Signing another response does not let an operator count twice
+        assert_eq!(
+            signatures.record(B256::repeat_byte(0xBB), operator(1)),
+            Err(SignatureError::DuplicateOperator(operator(1)))
+        );
+        assert_eq!(
+            signatures.signed_stake(&digest),
+            Some(&[U256::from(40)][..])
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use crate::Error;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::Write;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+/// Local JSON file a piece of operator state is persisted to, so it is resumed after
+/// a restart.
+#[derive(Debug)]
+pub struct JsonStore<T> {
+    path: PathBuf,
+    state: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for JsonStore<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.path.clone())
+    }
+}
+
+impl<T> JsonStore<T> {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            state: PhantomData,
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + Default> JsonStore<T> {
+    /// Loads the persisted state, the default state if nothing was persisted yet.
+    pub fn load(&self) -> Result<T, Error> {
+        match std::fs::read(&self.path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| Error::Storage(e.to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+            Err(e) => Err(Error::Storage(e.to_string())),
+        }
+    }
+
+    /// Replaces the persisted state. The file is written next to its destination
+    /// and renamed over it, so a crash never leaves a partial state behind.
+    pub fn save(&self, state: &T) -> Result<(), Error> {
+        let storage = |e: std::io::Error| Error::Storage(e.to_string());
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut file = tempfile::NamedTempFile::new_in(dir).map_err(storage)?;
+        let bytes = serde_json::to_vec_pretty(state).map_err(|e| Error::Storage(e.to_string()))?;
+        file.write_all(&bytes).map_err(storage)?;
+        file.persist(&self.path).map_err(|e| storage(e.error))?;
+        Ok(())
+    }
+}
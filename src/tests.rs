@@ -1,13 +1,18 @@
-use crate::constants::AGGREGATOR_PRIVATE_KEY;
+use crate::constants::{AGGREGATOR_PRIVATE_KEY, TOKEN_METADATA};
 use crate::contexts::aggregator::AggregatorContext;
 use crate::contexts::client::AggregatorClient;
 use crate::contexts::order::EigenOrderContext;
+use crate::jobs::amend_order::AmendOrderEventHandler;
 use crate::jobs::create_order::OrderEigenEventHandler;
 use crate::jobs::initialize_task::InitializeBlsTaskEventHandler;
-use crate::OrderBookTaskManager;
+use crate::jobs::trigger_orders::TriggerOrdersEventHandler;
+use crate::matching::engine::MatchingConfig;
+use crate::matching::order_type::OrderType;
+use crate::matching::trigger::TriggerType;
+use crate::{IOrderBookTaskManager, OrderBookTaskManager};
 use alloy_contract::{CallBuilder, CallDecoder};
 use alloy_network::{EthereumWallet, Ethereum};
-use alloy_primitives::{address, Address, Bytes, U256};
+use alloy_primitives::{address, Address, Bytes, B256, U256};
 use alloy_provider::Provider;
 use alloy_rpc_types::TransactionReceipt;
 use alloy_signer_local::PrivateKeySigner;
@@ -87,6 +92,10 @@ async fn run_eigenlayer_orderbook_test(
     let server_address = format!("{}:{}", "127.0.0.1", 8081);
     let eigen_client_context = EigenOrderContext {
         client: AggregatorClient::new(&server_address).unwrap(),
+        matching: MatchingConfig::from_env(),
+        fees: Default::default(),
+        batches: Default::default(),
+        tokens: Arc::new(tokio::sync::Mutex::new(TOKEN_METADATA.clone())),
         std_config: env.clone(),
     };
     let aggregator_context =
@@ -102,6 +111,9 @@ async fn run_eigenlayer_orderbook_test(
     );
     let initialize_task =
         InitializeBlsTaskEventHandler::new(contract.clone(), aggregator_context.clone());
+    let trigger_orders =
+        TriggerOrdersEventHandler::new(contract.clone(), aggregator_context.clone());
+    let amend_order = AmendOrderEventHandler::new(contract.clone(), eigen_client_context.clone());
     let order_eigen = OrderEigenEventHandler::new(contract.clone(), eigen_client_context);
 
     let mut test_env = EigenlayerBLSTestEnv::new(
@@ -111,7 +123,9 @@ async fn run_eigenlayer_orderbook_test(
     )
     .unwrap();
     test_env.add_job(initialize_task);
+    test_env.add_job(trigger_orders);
     test_env.add_job(order_eigen);
+    test_env.add_job(amend_order);
     test_env.add_background_service(aggregator_context);
 
     if exit_after_registration {
@@ -131,6 +145,18 @@ async fn run_eigenlayer_orderbook_test(
         timeout_duration,
     )
     .await;
+    let round_trips = match result {
+        Ok(Ok(())) => {
+            run_order_round_trips(
+                &http_endpoint,
+                task_manager_address,
+                harness.aggregator_account(),
+                successful_responses.clone(),
+            )
+            .await
+        }
+        _ => Ok(()),
+    };
 
     // Start the shutdown/cleanup process
     aggregator_context_clone.shutdown().await;
@@ -140,6 +166,9 @@ async fn run_eigenlayer_orderbook_test(
 
     match result {
         Ok(Ok(())) => {
+            if let Err(e) = round_trips {
+                panic!("Order round trips failed: {}", e);
+            }
             info!("Test completed successfully with {expected_responses} tasks responded to.");
         }
         _ => {
@@ -152,6 +181,177 @@ async fn run_eigenlayer_orderbook_test(
     }
 }
 
+/// Amends and cancels the spawned orders, then submits a stop order to `triggerOrders`,
+/// checking the contract state after each step and that the tasks created afterwards are
+/// still answered. Every spawned order belongs to the task generator, so nothing trades and
+/// the contract must refuse to fire the stop order.
+async fn run_order_round_trips(
+    http_endpoint: &str,
+    task_manager_address: Address,
+    aggregator_address: Address,
+    successful_responses: Arc<Mutex<usize>>,
+) -> Result<(), String> {
+    let generator = address!("15d34AAf54267DB7D7c367839AAf71A00a2C6A65");
+    let provider = get_provider_http(http_endpoint);
+    let task_manager = OrderBookTaskManager::new(task_manager_address, provider.clone());
+
+    // Both spawned orders rest once their tasks are answered
+    expect_responses(&successful_responses, 2).await?;
+
+    // A same-price size-down keeps the order's time priority
+    let before = order_at(&task_manager, 0).await?;
+    let receipt = get_receipt(
+        task_manager
+            .amendOrder(U256::ZERO, U256::from(3), U256::from(120))
+            .from(generator),
+    )
+    .await?;
+    ensure(receipt.status(), "amendOrder reverted")?;
+    let amended = order_at(&task_manager, 0).await?;
+    ensure(
+        amended.amount_owned == U256::from(3) && amended.amount_not_owned == U256::from(120),
+        format!(
+            "order 0 gives {} for {} after its amendment",
+            amended.amount_owned, amended.amount_not_owned
+        ),
+    )?;
+    ensure(
+        amended.timestamp == before.timestamp,
+        "order 0 lost its time priority",
+    )?;
+
+    let receipt = get_receipt(task_manager.cancelOrder(U256::from(1)).from(generator)).await?;
+    ensure(receipt.status(), "cancelOrder reverted")?;
+    ensure(
+        order_at(&task_manager, 1).await?.isCancelled,
+        "order 1 is not cancelled",
+    )?;
+
+    // A dormant stop order, matched against the amended and cancelled orders
+    let receipt = get_receipt(
+        task_manager
+            .createNewTask(
+                U256::from(5),
+                U256::from(200),
+                address!("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"),
+                address!("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"),
+                U256::from(5),
+                OrderType::Limit.into(),
+                U256::ZERO,
+                TriggerType::Stop.into(),
+                U256::from(1),
+                U256::from(40),
+                U256::ZERO,
+                105u32,
+                vec![].into(),
+            )
+            .from(generator),
+    )
+    .await?;
+    ensure(receipt.status(), "createNewTask reverted")?;
+    expect_responses(&successful_responses, 3).await?;
+    ensure(
+        task_manager
+            .allTaskResponses(2)
+            .call()
+            .await
+            .map_err(|e| e.to_string())?
+            ._0
+            != B256::ZERO,
+        "task 2 was not answered",
+    )?;
+    let orders = all_orders(&task_manager).await?;
+    ensure(
+        orders[0].amount_owned == U256::from(3) && !orders[0].isCancelled,
+        "the amended order changed after task 2",
+    )?;
+    ensure(
+        orders[1].isCancelled && orders[1].amount_owned == U256::from(5),
+        "the cancelled order changed after task 2",
+    )?;
+    ensure(
+        !orders[2].isTriggered && !orders[2].isCancelled && !orders[2].isFilled,
+        "the stop order is not dormant",
+    )?;
+
+    // No trade has crossed the stop order, the contract leaves it dormant
+    let receipt = get_receipt(
+        task_manager
+            .triggerOrders(vec![U256::from(2)], 105u32, vec![].into())
+            .from(aggregator_address),
+    )
+    .await?;
+    ensure(receipt.status(), "triggerOrders reverted")?;
+    ensure(
+        task_manager
+            .taskNumber()
+            .call()
+            .await
+            .map_err(|e| e.to_string())?
+            ._0
+            == 3,
+        "triggerOrders created a task for an order no trade crossed",
+    )?;
+    let stop = order_at(&task_manager, 2).await?;
+    ensure(
+        !stop.isTriggered && !stop.isCancelled,
+        "the stop order was retired without a crossing trade",
+    )?;
+
+    Ok(())
+}
+
+async fn all_orders<T, P>(
+    task_manager: &OrderBookTaskManager::OrderBookTaskManagerInstance<T, P>,
+) -> Result<Vec<IOrderBookTaskManager::Order>, String>
+where
+    T: Transport + Clone,
+    P: Provider<T, Ethereum>,
+{
+    Ok(task_manager
+        .getAllOrders()
+        .call()
+        .await
+        .map_err(|e| e.to_string())?
+        ._0)
+}
+
+async fn order_at<T, P>(
+    task_manager: &OrderBookTaskManager::OrderBookTaskManagerInstance<T, P>,
+    index: usize,
+) -> Result<IOrderBookTaskManager::Order, String>
+where
+    T: Transport + Clone,
+    P: Provider<T, Ethereum>,
+{
+    all_orders(task_manager)
+        .await?
+        .get(index)
+        .cloned()
+        .ok_or_else(|| format!("order {} does not exist", index))
+}
+
+async fn expect_responses(
+    successful_responses: &Arc<Mutex<usize>>,
+    count: usize,
+) -> Result<(), String> {
+    wait_for_responses(
+        successful_responses.clone(),
+        count,
+        Duration::from_secs(300),
+    )
+    .await
+    .map_err(|_| format!("timed out waiting for {} task responses", count))?
+}
+
+fn ensure(condition: bool, message: impl Into<String>) -> Result<(), String> {
+    if condition {
+        Ok(())
+    } else {
+        Err(message.into())
+    }
+}
+
 pub async fn deploy_task_manager(harness: &EigenlayerTestHarness) -> Address {
     let env = harness.env().clone();
     let http_endpoint = &env.http_rpc_endpoint;
@@ -231,7 +431,7 @@ pub async fn setup_task_spawner(
             info!("Creating a new task...");
             let create_task_receipt = get_receipt(
                 task_manager
-                    .createNewTask(U256::from(5), U256::from(200), address!("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"), address!("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"), U256::from(5), 105u32, vec![].into())
+                    .createNewTask(U256::from(5), U256::from(200), address!("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"), address!("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"), U256::from(5), OrderType::Limit.into(), U256::ZERO, TriggerType::None.into(), U256::ZERO, U256::ZERO, U256::ZERO, 105u32, vec![].into())
                     .from(address!("15d34AAf54267DB7D7c367839AAf71A00a2C6A65"))
             )
             .await;
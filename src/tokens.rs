@@ -0,0 +1,208 @@
+use crate::matching::price::Price;
+use crate::{Error, IERC20Metadata};
+use alloy_primitives::{Address, U256};
+use blueprint_sdk::eigensdk::common::get_provider;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Number of fractional digits shown when displaying a price.
+const PRICE_PRECISION: usize = 6;
+
+/// Decimals and symbol of an ERC20 token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenMetadata {
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+/// Metadata of the tokens traded in the orderbook.
+///
+/// Tokens can be configured up front, see [`crate::constants::TOKEN_METADATA`], or
+/// resolved from the token contract the first time they are seen.
+#[derive(Debug, Clone, Default)]
+pub struct TokenRegistry {
+    tokens: HashMap<Address, TokenMetadata>,
+}
+
+impl TokenRegistry {
+    pub fn get(&self, token: &Address) -> Option<&TokenMetadata> {
+        self.tokens.get(token)
+    }
+
+    pub fn insert(&mut self, token: Address, metadata: TokenMetadata) {
+        self.tokens.insert(token, metadata);
+    }
+
+    /// Returns the metadata of `token`, calling `decimals()` and `symbol()` on the
+    /// token contract if it is not known yet.
+    pub async fn resolve(
+        &mut self,
+        http_rpc_url: &str,
+        token: Address,
+    ) -> Result<TokenMetadata, Error> {
+        if let Some(metadata) = self.tokens.get(&token) {
+            return Ok(metadata.clone());
+        }
+
+        let provider = get_provider(http_rpc_url);
+        let contract = IERC20Metadata::new(token, provider);
+        let decimals = contract
+            .decimals()
+            .call()
+            .await
+            .map_err(|e| Error::Chain(e.to_string()))?
+            ._0;
+        let symbol = contract
+            .symbol()
+            .call()
+            .await
+            .map_err(|e| Error::Chain(e.to_string()))?
+            ._0;
+
+        let metadata = TokenMetadata { symbol, decimals };
+        self.tokens.insert(token, metadata.clone());
+        Ok(metadata)
+    }
+
+    /// Converts a price in base units of `numerator_token` per base unit of
+    /// `denominator_token` into whole tokens, or `None` if either token is unknown.
+    pub fn normalize(
+        &self,
+        price: Price,
+        numerator_token: &Address,
+        denominator_token: &Address,
+    ) -> Option<Price> {
+        price.normalize(
+            self.get(numerator_token)?.decimals,
+            self.get(denominator_token)?.decimals,
+        )
+    }
+
+    /// Human readable price, e.g. `2000.5 USDC/WETH`, falling back to base units
+    /// if either token is unknown.
+    pub fn format_price(
+        &self,
+        price: Price,
+        numerator_token: &Address,
+        denominator_token: &Address,
+    ) -> String {
+        match (
+            self.normalize(price, numerator_token, denominator_token),
+            self.get(numerator_token),
+            self.get(denominator_token),
+        ) {
+            (Some(normalized), Some(numerator), Some(denominator)) => format!(
+                "{} {}/{}",
+                normalized.to_decimal_string(PRICE_PRECISION),
+                numerator.symbol,
+                denominator.symbol
+            ),
+            _ => format!(
+                "{} {}/{} (base units)",
+                price.to_decimal_string(PRICE_PRECISION),
+                numerator_token,
+                denominator_token
+            ),
+        }
+    }
+
+    /// Converts a whole token amount into base units, or `None` if the token is
+    /// unknown or the amount does not fit in a `U256`.
+    pub fn to_base_units(&self, token: &Address, amount: u64) -> Option<U256> {
+        let scale = U256::from(10).checked_pow(U256::from(self.get(token)?.decimals))?;
+        U256::from(amount).checked_mul(scale)
+    }
+}
+
+/// Parses a comma separated list of `address:symbol:decimals` entries.
+impl FromStr for TokenRegistry {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut registry = TokenRegistry::default();
+
+        for entry in s
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let invalid = || Error::Config(format!("Invalid token metadata: {}", entry));
+            let mut parts = entry.split(':');
+            let (Some(address), Some(symbol), Some(decimals), None) =
+                (parts.next(), parts.next(), parts.next(), parts.next())
+            else {
+                return Err(invalid());
+            };
+
+            registry.insert(
+                address.trim().parse().map_err(|_| invalid())?,
+                TokenMetadata {
+                    symbol: symbol.trim().to_string(),
+                    decimals: decimals.trim().parse().map_err(|_| invalid())?,
+                },
+            );
+        }
+
+        Ok(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WETH: Address = Address::repeat_byte(0xAA);
+    const USDC: Address = Address::repeat_byte(0xBB);
+    const DAI: Address = Address::repeat_byte(0xCC);
+
+    fn registry() -> TokenRegistry {
+        format!("{}:WETH:18, {}:USDC:6", WETH, USDC)
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_registry_from_config() {
+        let registry = registry();
+        assert_eq!(
+            registry.get(&USDC),
+            Some(&TokenMetadata {
+                symbol: "USDC".to_string(),
+                decimals: 6,
+            })
+        );
+        assert!(registry.get(&DAI).is_none());
+
+        assert!("".parse::<TokenRegistry>().unwrap().get(&WETH).is_none());
+        assert!("0x1234:WETH:18".parse::<TokenRegistry>().is_err());
+        assert!(format!("{}:WETH", WETH).parse::<TokenRegistry>().is_err());
+        assert!(format!("{}:WETH:eighteen", WETH)
+            .parse::<TokenRegistry>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_prices_are_normalized_by_decimals() {
+        let registry = registry();
+        // 4000 USDC for 2 WETH, in base units
+        let price = Price::new(
+            registry.to_base_units(&USDC, 4000).unwrap(),
+            registry.to_base_units(&WETH, 2).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            registry.normalize(price, &USDC, &WETH),
+            Price::new(U256::from(2000), U256::from(1))
+        );
+        assert_eq!(registry.format_price(price, &USDC, &WETH), "2000 USDC/WETH");
+        assert_eq!(
+            registry.format_price(price.inverse(), &WETH, &USDC),
+            "0.0005 WETH/USDC"
+        );
+        assert!(registry.normalize(price, &DAI, &WETH).is_none());
+        assert!(registry
+            .format_price(price, &DAI, &WETH)
+            .ends_with("(base units)"));
+    }
+}
@@ -0,0 +1,658 @@
+use crate::constants::{MARKET_SPECS, TASK_QUORUM_NUMBERS, TASK_QUORUM_THRESHOLD_PERCENTAGE};
+use crate::jobs::create_order::orders_at;
+use crate::matching::order_type::OrderType;
+use crate::matching::price::narrow;
+use crate::matching::trigger::TriggerType;
+use crate::store::JsonStore;
+use crate::IOrderBookTaskManager::{Order, TaskResponse};
+use crate::{Error, OrderBookTaskManager};
+use alloy_primitives::{Address, U256, U512};
+use alloy_provider::Provider;
+use blueprint_sdk::logging::{error, info, warn};
+use blueprint_sdk::utils::evm::get_provider_http;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tokio::time::interval;
+
+/// How often the scheduler checks whether a slice is due.
+const SLICE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Slippage of every child order, as a percentage of its limit price.
+const CHILD_SLIPPAGE: u64 = 5;
+
+/// A parent order to be executed in equal slices over a time horizon.
+///
+/// Amounts are in base units and give the parent's limit price, which every child
+/// order respects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TwapSpec {
+    pub token_owned: Address,
+    pub token_not_owned: Address,
+    pub amount_owned: U256,
+    pub amount_not_owned: U256,
+    pub slices: u32,
+    /// Seconds between the first and the last slice.
+    pub horizon: u64,
+}
+
+/// Parent orders submitted to the scheduler.
+#[derive(Debug, Clone, Default)]
+pub struct TwapSpecs(Vec<TwapSpec>);
+
+impl TwapSpecs {
+    pub fn iter(&self) -> impl Iterator<Item = &TwapSpec> {
+        self.0.iter()
+    }
+}
+
+/// Parses a comma separated list of
+/// `token_owned:token_not_owned:amount_owned:amount_not_owned:slices:horizon_secs` entries.
+impl FromStr for TwapSpecs {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut specs = Vec::new();
+
+        for entry in s
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let invalid = || Error::Config(format!("Invalid TWAP order: {}", entry));
+            let parts: Vec<&str> = entry.split(':').map(str::trim).collect();
+            let [token_owned, token_not_owned, amount_owned, amount_not_owned, slices, horizon] =
+                parts[..]
+            else {
+                return Err(invalid());
+            };
+
+            let spec = TwapSpec {
+                token_owned: token_owned.parse().map_err(|_| invalid())?,
+                token_not_owned: token_not_owned.parse().map_err(|_| invalid())?,
+                amount_owned: U256::from_str(amount_owned).map_err(|_| invalid())?,
+                amount_not_owned: U256::from_str(amount_not_owned).map_err(|_| invalid())?,
+                slices: slices.parse().map_err(|_| invalid())?,
+                horizon: horizon.parse().map_err(|_| invalid())?,
+            };
+            if spec.token_owned == spec.token_not_owned
+                || spec.amount_owned.is_zero()
+                || spec.amount_not_owned.is_zero()
+                || spec.slices == 0
+            {
+                return Err(invalid());
+            }
+            specs.push(spec);
+        }
+
+        Ok(Self(specs))
+    }
+}
+
+/// Amounts of the next child order of a parent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slice {
+    pub amount_owned: U256,
+    pub amount_not_owned: U256,
+}
+
+/// A child order created for a slice, as last read from the contract.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChildOrder {
+    /// Index of the child's task, and of the child in the orderbook.
+    pub task_index: u32,
+    pub amount_owned: U256,
+    pub amount_not_owned: U256,
+    pub remaining_owned: U256,
+    pub remaining_not_owned: U256,
+    /// False once the child can no longer trade.
+    pub live: bool,
+}
+
+impl ChildOrder {
+    fn update(&mut self, order: &Order) {
+        self.remaining_owned = order.amount_owned;
+        self.remaining_not_owned = order.amount_not_owned;
+        self.live = !order.isFilled && !order.isCancelled;
+    }
+
+    pub fn given(&self) -> U256 {
+        self.amount_owned.saturating_sub(self.remaining_owned)
+    }
+
+    pub fn received(&self) -> U256 {
+        self.amount_not_owned
+            .saturating_sub(self.remaining_not_owned)
+    }
+}
+
+/// A parent order being executed, persisted between restarts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TwapParent {
+    pub spec: TwapSpec,
+    /// Unix time of the first slice.
+    pub start: u64,
+    /// Number of slices already due, including those skipped.
+    pub sent: u32,
+    pub children: Vec<ChildOrder>,
+}
+
+impl TwapParent {
+    pub fn new(spec: TwapSpec, start: u64) -> Self {
+        Self {
+            spec,
+            start,
+            sent: 0,
+            children: Vec::new(),
+        }
+    }
+
+    /// Unix time slice `slice` is due at, slices are spread evenly over the horizon.
+    pub fn due(&self, slice: u32) -> u64 {
+        let last = u64::from(self.spec.slices.saturating_sub(1)).max(1);
+        self.start + self.spec.horizon * u64::from(slice) / last
+    }
+
+    /// Total amounts given and received by the children so far.
+    pub fn filled(&self) -> (U256, U256) {
+        self.children
+            .iter()
+            .fold((U256::ZERO, U256::ZERO), |(given, received), child| {
+                (
+                    given.saturating_add(child.given()),
+                    received.saturating_add(child.received()),
+                )
+            })
+    }
+
+    /// What is left to trade, net of what live children may still trade.
+    fn unscheduled(&self) -> (U256, U256) {
+        let (mut owned, mut not_owned) = self.filled();
+        for child in self.children.iter().filter(|child| child.live) {
+            owned = owned.saturating_add(child.remaining_owned);
+            not_owned = not_owned.saturating_add(child.remaining_not_owned);
+        }
+        (
+            self.spec.amount_owned.saturating_sub(owned),
+            self.spec.amount_not_owned.saturating_sub(not_owned),
+        )
+    }
+
+    /// Returns true if a slice is left to send and it is due at `now`.
+    pub fn is_due(&self, now: u64) -> bool {
+        self.sent < self.spec.slices && now >= self.due(self.sent)
+    }
+
+    /// The next child order if its slice is due at `now`.
+    ///
+    /// Each slice is what is left to receive spread evenly over the slices left, so
+    /// whatever earlier children did not fill is caught up by the later ones. The child
+    /// gives at most the parent's limit price for it.
+    pub fn next_slice(&self, now: u64) -> Option<Slice> {
+        if !self.is_due(now) {
+            return None;
+        }
+        let (budget, to_receive) = self.unscheduled();
+        let amount_not_owned = to_receive.div_ceil(U256::from(self.spec.slices - self.sent));
+        let amount_owned = narrow(
+            U512::from(self.spec.amount_owned) * U512::from(amount_not_owned)
+                / U512::from(self.spec.amount_not_owned),
+        )?
+        .min(budget);
+        if amount_owned.is_zero() || amount_not_owned.is_zero() {
+            return None;
+        }
+        Some(Slice {
+            amount_owned,
+            amount_not_owned,
+        })
+    }
+
+    /// Records the child order created for the slice that was due.
+    pub fn record_child(&mut self, task_index: u32, slice: Slice) {
+        self.sent += 1;
+        self.children.push(ChildOrder {
+            task_index,
+            amount_owned: slice.amount_owned,
+            amount_not_owned: slice.amount_not_owned,
+            remaining_owned: slice.amount_owned,
+            remaining_not_owned: slice.amount_not_owned,
+            live: true,
+        });
+    }
+
+    /// Moves past a slice that could not be sent, its size is spread over the rest.
+    pub fn skip_slice(&mut self) {
+        self.sent += 1;
+    }
+
+    /// Returns true if an accepted response may have changed any child.
+    pub fn is_touched_by(&self, response: &TaskResponse) -> bool {
+        let mut indices = std::iter::once(U256::from(response.referenceTaskIndex)).chain(
+            response
+                .fills
+                .iter()
+                .chain(&response.selfTradeUpdates)
+                .chain(&response.ringUpdates)
+                .map(|fill| fill.orderIndex),
+        );
+        indices.any(|index| {
+            self.children
+                .iter()
+                .any(|child| U256::from(child.task_index) == index)
+        })
+    }
+
+    /// Updates every child from `orders`, the contract's orders at their own index. The
+    /// contract may have dropped part of a response, so children are always read back
+    /// rather than taken from the response. Returns true if any child changed.
+    pub fn sync(&mut self, orders: &[Order]) -> bool {
+        let mut changed = false;
+        for child in &mut self.children {
+            let Some(order) = orders.get(child.task_index as usize) else {
+                continue;
+            };
+            let before = child.clone();
+            child.update(order);
+            changed |= *child != before;
+        }
+        changed
+    }
+
+    /// Returns true once no slice is left to send and no child can trade anymore.
+    pub fn is_done(&self) -> bool {
+        let (budget, to_receive) = self.unscheduled();
+        let nothing_left =
+            self.sent >= self.spec.slices || budget.is_zero() || to_receive.is_zero();
+        nothing_left && self.children.iter().all(|child| !child.live)
+    }
+}
+
+/// Updates the children of every parent from the contract's `orders`. Returns true if
+/// any child changed.
+fn sync_children(parents: &mut [TwapParent], orders: &[Order]) -> bool {
+    let mut changed = false;
+    for parent in parents {
+        changed |= parent.sync(orders);
+    }
+    changed
+}
+
+/// Local file the state of every parent order is persisted to.
+pub type TwapStore = JsonStore<Vec<TwapParent>>;
+
+/// Slices parent orders into child tasks over their horizon.
+///
+/// Children are immediate-or-cancel orders created with `createNewTask`, signed by the
+/// quorums in `TASK_QUORUM_NUMBERS`. Their fills are read back from the contract after
+/// every `TaskResponded` event touching them, so the size of every later slice adapts
+/// to what the parent has actually traded. The state of every parent is persisted
+/// after each change and resumed on restart, when the children are read back again to
+/// catch up on the responses missed meanwhile.
+pub struct TwapScheduler {
+    parents: Arc<Mutex<Vec<TwapParent>>>,
+    store: TwapStore,
+}
+
+impl TwapScheduler {
+    /// Resumes the parents persisted in `store`, or starts `specs` at `now` if there
+    /// are none.
+    pub fn load_or_start(store: TwapStore, specs: &TwapSpecs, now: u64) -> Result<Self, Error> {
+        let mut parents = store.load()?;
+        if parents.is_empty() {
+            parents = specs
+                .iter()
+                .map(|spec| TwapParent::new(*spec, now))
+                .collect();
+            store.save(&parents)?;
+        }
+        Ok(Self {
+            parents: Arc::new(Mutex::new(parents)),
+            store,
+        })
+    }
+
+    /// Runs until every parent is done, creating child tasks from `generator`.
+    pub async fn run(
+        self,
+        rpc_endpoint: &str,
+        task_manager: Address,
+        generator: Address,
+    ) -> Result<(), Error> {
+        let provider = get_provider_http(rpc_endpoint);
+        let contract = OrderBookTaskManager::new(task_manager, provider);
+
+        let poller = contract
+            .TaskResponded_filter()
+            .watch()
+            .await
+            .map_err(|e| Error::Chain(e.to_string()))?;
+        // Responses accepted while the scheduler was down are only in the contract
+        let block = contract
+            .provider()
+            .get_block_number()
+            .await
+            .map_err(|e| Error::Chain(e.to_string()))?;
+        let orders = orders_at(rpc_endpoint, task_manager, block).await?;
+        {
+            let mut parents = self.parents.lock().await;
+            if sync_children(&mut parents, &orders) {
+                self.store.save(&parents)?;
+            }
+        }
+        let watcher = {
+            let parents = Arc::clone(&self.parents);
+            let store = self.store.clone();
+            let rpc_endpoint = rpc_endpoint.to_string();
+            tokio::spawn(async move {
+                let mut responses = poller.into_stream();
+                while let Some(response) = responses.next().await {
+                    let (event, log) = match response {
+                        Ok(response) => response,
+                        Err(e) => {
+                            warn!("Failed to decode task response: {}", e);
+                            continue;
+                        }
+                    };
+                    let mut parents = parents.lock().await;
+                    if !parents
+                        .iter()
+                        .any(|parent| parent.is_touched_by(&event.taskResponse))
+                    {
+                        continue;
+                    }
+                    let Some(block) = log.block_number else {
+                        warn!("Ignoring a task response without a block number");
+                        continue;
+                    };
+                    let orders = match orders_at(&rpc_endpoint, task_manager, block).await {
+                        Ok(orders) => orders,
+                        Err(e) => {
+                            error!("Failed to read back the TWAP children: {}", e);
+                            continue;
+                        }
+                    };
+                    if sync_children(&mut parents, &orders) {
+                        if let Err(e) = store.save(&parents) {
+                            error!("Failed to persist TWAP orders: {}", e);
+                        }
+                    }
+                }
+            })
+        };
+
+        let mut ticker = interval(SLICE_POLL_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|e| Error::Runtime(e.to_string()))?
+                .as_secs();
+
+            // The lock is held until each child is recorded, so its response is never
+            // applied before the child is known
+            let mut parents = self.parents.lock().await;
+            if parents.iter().all(TwapParent::is_done) {
+                break;
+            }
+            for (id, parent) in parents.iter_mut().enumerate() {
+                if !parent.is_due(now) {
+                    continue;
+                }
+                // Nothing left to trade at the parent's price this slice
+                let Some(slice) = parent.next_slice(now) else {
+                    parent.skip_slice();
+                    continue;
+                };
+                let spec = parent.spec;
+                if let Err(rejection) = MARKET_SPECS.validate_new_order(
+                    slice.amount_owned,
+                    slice.amount_not_owned,
+                    spec.token_not_owned,
+                    spec.token_owned,
+                    OrderType::ImmediateOrCancel,
+                ) {
+                    warn!(
+                        "Skipping slice {} of TWAP order {} rejected by the market specification: {:?}",
+                        parent.sent, id, rejection
+                    );
+                    parent.skip_slice();
+                    continue;
+                }
+
+                let call = contract
+                    .createNewTask(
+                        slice.amount_owned,
+                        slice.amount_not_owned,
+                        spec.token_not_owned,
+                        spec.token_owned,
+                        U256::from(CHILD_SLIPPAGE),
+                        OrderType::ImmediateOrCancel.into(),
+                        U256::ZERO,
+                        TriggerType::None.into(),
+                        U256::ZERO,
+                        U256::ZERO,
+                        U256::ZERO,
+                        *TASK_QUORUM_THRESHOLD_PERCENTAGE,
+                        TASK_QUORUM_NUMBERS.clone(),
+                    )
+                    .from(generator);
+                // A slice that failed to send is retried on the next tick
+                let receipt = match call.send().await {
+                    Ok(pending) => match pending.get_receipt().await {
+                        Ok(receipt) => receipt,
+                        Err(e) => {
+                            error!("Failed to create a task for TWAP order {}: {}", id, e);
+                            continue;
+                        }
+                    },
+                    Err(e) => {
+                        error!("Failed to create a task for TWAP order {}: {}", id, e);
+                        continue;
+                    }
+                };
+                let Some(task_index) = receipt.inner.logs().iter().find_map(|log| {
+                    log.log_decode::<OrderBookTaskManager::NewTaskCreated>()
+                        .ok()
+                        .map(|log| log.inner.data.taskIndex)
+                }) else {
+                    warn!("Task creation failed for TWAP order {}: {:?}", id, receipt);
+                    continue;
+                };
+
+                info!(
+                    "Created task {} for slice {} of TWAP order {}: {} for {}",
+                    task_index, parent.sent, id, slice.amount_owned, slice.amount_not_owned
+                );
+                parent.record_child(task_index, slice);
+            }
+            self.store.save(&parents)?;
+        }
+
+        watcher.abort();
+        for (id, parent) in self.parents.lock().await.iter().enumerate() {
+            let (given, received) = parent.filled();
+            info!("TWAP order {} done: gave {} for {}", id, given, received);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matching::test_support::{order, USDC, WETH};
+    use crate::IOrderBookTaskManager::Fill;
+
+    fn spec() -> TwapSpec {
+        TwapSpec {
+            token_owned: USDC,
+            token_not_owned: WETH,
+            amount_owned: U256::from(400),
+            amount_not_owned: U256::from(100),
+            slices: 4,
+            horizon: 30,
+        }
+    }
+
+    fn child_order(remaining_owned: u64, remaining_not_owned: u64) -> Order {
+        Order {
+            orderType: OrderType::ImmediateOrCancel.into(),
+            // Immediate-or-cancel children never rest
+            isCancelled: true,
+            ..order(
+                0,
+                USDC,
+                remaining_owned,
+                WETH,
+                remaining_not_owned,
+                CHILD_SLIPPAGE,
+            )
+        }
+    }
+
+    fn response(task_index: u32, new_order: Order) -> TaskResponse {
+        TaskResponse {
+            referenceTaskIndex: task_index,
+            outcome: 1,
+            rejectionReason: 0,
+            newOrder: new_order,
+            fills: Vec::new(),
+            takerFee: U256::ZERO,
+            selfTradeOutcome: 0,
+            selfTradeUpdates: Vec::new(),
+            ringUpdates: Vec::new(),
+            isBatch: false,
+            clearingPriceNumerator: U256::ZERO,
+            clearingPriceDenominator: U256::ZERO,
+            batchTasks: Vec::new(),
+        }
+    }
+
+    /// The contract's orders with `child` at `index`, other slots hold unrelated orders.
+    fn orders_with(index: usize, child: Order) -> Vec<Order> {
+        let mut orders = vec![order(9, WETH, 1, USDC, 1, 5); index + 1];
+        orders[index] = child;
+        orders
+    }
+
+    #[test]
+    fn test_parses_specs() {
+        let specs: TwapSpecs = format!("{}:{}:400:100:4:30", USDC, WETH).parse().unwrap();
+        assert_eq!(specs.iter().collect::<Vec<_>>(), vec![&spec()]);
+
+        assert!(format!("{}:{}:400:100:0:30", USDC, WETH)
+            .parse::<TwapSpecs>()
+            .is_err());
+        assert!(format!("{}:{}:400:100", USDC, WETH)
+            .parse::<TwapSpecs>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_slices_are_spread_over_the_horizon() {
+        let parent = TwapParent::new(spec(), 1_000);
+        assert_eq!(parent.due(0), 1_000);
+        assert_eq!(parent.due(3), 1_030);
+        assert_eq!(
+            parent.next_slice(1_000),
+            Some(Slice {
+                amount_owned: U256::from(100),
+                amount_not_owned: U256::from(25),
+            })
+        );
+
+        let mut parent = parent;
+        parent.record_child(7, parent.next_slice(1_000).unwrap());
+        assert_eq!(parent.next_slice(1_009), None);
+        assert!(parent.next_slice(1_010).is_some());
+    }
+
+    #[test]
+    fn test_later_slices_catch_up_on_unfilled_children() {
+        let mut parent = TwapParent::new(spec(), 0);
+        parent.record_child(7, parent.next_slice(0).unwrap());
+
+        // The first child only receives 5 of its 25 and the rest is cancelled
+        assert!(parent.is_touched_by(&response(7, child_order(80, 20))));
+        assert!(!parent.is_touched_by(&response(8, child_order(0, 0))));
+        assert!(parent.sync(&orders_with(7, child_order(80, 20))));
+        assert!(!parent.sync(&orders_with(7, child_order(80, 20))));
+        assert_eq!(parent.filled(), (U256::from(20), U256::from(5)));
+
+        // 95 left to receive over 3 slices
+        assert_eq!(
+            parent.next_slice(10),
+            Some(Slice {
+                amount_owned: U256::from(128),
+                amount_not_owned: U256::from(32),
+            })
+        );
+        assert!(!parent.is_done());
+    }
+
+    #[test]
+    fn test_resting_children_are_tracked_through_fills() {
+        let mut parent = TwapParent::new(spec(), 0);
+        parent.record_child(7, parent.next_slice(0).unwrap());
+
+        let mut resting = child_order(100, 25);
+        resting.isCancelled = false;
+        parent.sync(&orders_with(7, resting));
+        // A live child counts towards the size already scheduled
+        assert_eq!(
+            parent.next_slice(10).unwrap().amount_not_owned,
+            U256::from(25)
+        );
+
+        let mut filled = child_order(0, 0);
+        filled.isCancelled = false;
+        filled.isFilled = true;
+        let mut later = response(9, child_order(0, 0));
+        later.fills.push(Fill {
+            orderIndex: U256::from(7),
+            newOrder: filled.clone(),
+            makerFee: U256::ZERO,
+        });
+        assert!(parent.is_touched_by(&later));
+        assert!(parent.sync(&orders_with(7, filled)));
+        assert_eq!(parent.filled(), (U256::from(100), U256::from(25)));
+    }
+
+    #[test]
+    fn test_children_are_read_back_on_resume() {
+        let mut parent = TwapParent::new(spec(), 0);
+        parent.record_child(7, parent.next_slice(0).unwrap());
+
+        // The contract does not hold the child yet
+        assert!(!parent.sync(&orders_with(3, child_order(0, 0))));
+        assert_eq!(parent.filled(), (U256::ZERO, U256::ZERO));
+
+        // Its response was accepted while the scheduler was down
+        assert!(sync_children(
+            std::slice::from_mut(&mut parent),
+            &orders_with(7, child_order(40, 10))
+        ));
+        assert_eq!(parent.filled(), (U256::from(60), U256::from(15)));
+        assert!(!parent.children[0].live);
+    }
+
+    #[test]
+    fn test_state_survives_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = TwapStore::new(dir.path().join("twap.json"));
+        assert!(store.load().unwrap().is_empty());
+
+        let specs = TwapSpecs(vec![spec()]);
+        let scheduler = TwapScheduler::load_or_start(store.clone(), &specs, 5).unwrap();
+        let mut parents = scheduler.parents.try_lock().unwrap().clone();
+        parents[0].record_child(7, parents[0].next_slice(5).unwrap());
+        store.save(&parents).unwrap();
+
+        // Persisted parents take precedence over the configured ones
+        let resumed = TwapScheduler::load_or_start(store, &TwapSpecs::default(), 50).unwrap();
+        assert_eq!(*resumed.parents.try_lock().unwrap(), parents);
+    }
+}